@@ -38,6 +38,7 @@ fn startup(mut commands: Commands, assets: Res<AssetServer>) {
             Tileset {
                 image: assets.load("square/atlas.tileset.ron"),
                 tile_size: UVec2::splat(8),
+                ..default()
             },
         ))
         .with_related_entities::<TileOf>(|t| {
@@ -7,26 +7,32 @@ use bevy_pancam::{PanCam, PanCamPlugin};
 use bevy_tilemap::prelude::*;
 
 fn main() {
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "Tilemap".to_string(),
-                        ..default()
-                    }),
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Tilemap".to_string(),
                     ..default()
-                })
-                .set(ImagePlugin::default_nearest()),
-        )
-        .add_plugins(FpsOverlayPlugin::default())
-        .add_plugins(RemotePlugin::default())
-        .add_plugins(RemoteHttpPlugin::default())
-        .add_plugins(PanCamPlugin)
-        .add_plugins(TilemapPlugin)
-        .add_systems(Startup, startup)
-        // .add_observer(on_add_tilemap_chunk)
-        .run();
+                }),
+                ..default()
+            })
+            .set(ImagePlugin::default_nearest()),
+    )
+    .add_plugins(FpsOverlayPlugin::default())
+    .add_plugins(RemotePlugin::default())
+    .add_plugins(RemoteHttpPlugin::default())
+    .add_plugins(PanCamPlugin)
+    .add_plugins(TilemapPlugin)
+    .add_systems(Startup, startup);
+
+    // Press G to show the bounds used to cull each chunk, which needs the `gizmos` feature
+    #[cfg(feature = "gizmos")]
+    app.add_systems(Update, toggle_chunk_aabbs);
+    #[cfg(not(feature = "gizmos"))]
+    info!("Run with `--features gizmos` and press G to show the chunk bounds");
+
+    app.run();
 }
 
 fn startup(mut commands: Commands, assets: Res<AssetServer>) {
@@ -35,6 +41,7 @@ fn startup(mut commands: Commands, assets: Res<AssetServer>) {
     let tileset = Tileset {
         image: assets.load("isometric/atlas.tileset.ron"),
         tile_size: UVec2::splat(32),
+        ..default()
     };
 
     commands
@@ -92,8 +99,24 @@ fn startup(mut commands: Commands, assets: Res<AssetServer>) {
     ));
 }
 
-fn on_add_tilemap_chunk(trigger: Trigger<OnAdd, TilemapChunk>, mut commands: Commands) {
-    commands
-        .entity(trigger.target())
-        .insert(ShowAabbGizmo::default());
+#[cfg(feature = "gizmos")]
+fn toggle_chunk_aabbs(
+    keys: Res<ButtonInput<KeyCode>>,
+    tilemap_layers: Query<(Entity, Has<ShowTilemapChunkAabbs>), With<TilemapLayer>>,
+    mut commands: Commands,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    for (tilemap_layer, shown) in &tilemap_layers {
+        if shown {
+            commands
+                .entity(tilemap_layer)
+                .remove::<ShowTilemapChunkAabbs>();
+        } else {
+            commands
+                .entity(tilemap_layer)
+                .insert(ShowTilemapChunkAabbs::default());
+        }
+    }
 }
@@ -0,0 +1,163 @@
+use core::time::Duration;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    platform::collections::HashSet,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use super::{
+    AdditionalTilesets, MAX_TILESETS, TilemapChunkMaterial, TilemapLayer, Tileset,
+    iter_layer_tilesets,
+};
+
+/// Width of the animation data texture, in texels.
+const ANIMATION_DATA_WIDTH: u32 = 256;
+
+/// A single frame of a [`TileAnimation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub struct TileAnimationFrame {
+    /// Index of the frame's texture in the tileset.
+    pub tileset_index: u16,
+    /// How long the frame is shown for.
+    pub duration: Duration,
+}
+
+/// An animation that cycles a tile through several textures of its tileset.
+///
+/// Animations are played back on the GPU, so animated tiles don't need to be touched
+/// after they have been written to the [`TileStorage`](crate::TileStorage).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Reflect)]
+pub struct TileAnimation {
+    pub frames: Vec<TileAnimationFrame>,
+}
+
+impl TileAnimation {
+    /// Creates an animation showing each tileset index in `frames` for `frame_duration`.
+    pub fn from_frames(frames: impl IntoIterator<Item = u16>, frame_duration: Duration) -> Self {
+        Self {
+            frames: frames
+                .into_iter()
+                .map(|tileset_index| TileAnimationFrame {
+                    tileset_index,
+                    duration: frame_duration,
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates an animation showing the tileset indices in `range` for `frame_duration` each.
+    pub fn from_range(range: core::ops::Range<u16>, frame_duration: Duration) -> Self {
+        Self::from_frames(range, frame_duration)
+    }
+
+    /// Returns the total duration of one loop of the animation.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Texture holding the [`Tileset::animations`] of all tilesets in a tilemap layer, shared by
+/// all of its chunks.
+#[derive(Component, Clone, Debug, Deref)]
+pub struct TilesetAnimationData(pub Handle<Image>);
+
+type AnimatedTilemapLayer = (
+    Entity,
+    &'static TilemapLayer,
+    &'static Tileset,
+    Option<&'static AdditionalTilesets>,
+    Option<&'static TilesetAnimationData>,
+);
+
+/// Filter for tilemap layers whose tilesets changed.
+pub(crate) type TilesetsChanged = Or<(Changed<Tileset>, Changed<AdditionalTilesets>)>;
+
+pub(crate) fn update_tileset_animation_data(
+    tilemap_layer_query: Query<AnimatedTilemapLayer, TilesetsChanged>,
+    chunk_query: Query<&MeshMaterial2d<TilemapChunkMaterial>>,
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut chunk_materials: ResMut<Assets<TilemapChunkMaterial>>,
+) {
+    for (tilemap_layer_entity, tilemap_layer, tileset, additional_tilesets, animation_data) in
+        tilemap_layer_query
+    {
+        let image = make_animation_data_image(iter_layer_tilesets(tileset, additional_tilesets));
+        match animation_data {
+            Some(animation_data) => {
+                images.insert(animation_data.id(), image);
+
+                // Bind groups keep the old texture until the materials using it are modified
+                let material_ids: HashSet<_> = tilemap_layer
+                    .chunks
+                    .values()
+                    .filter_map(|chunk_entity| chunk_query.get(*chunk_entity).ok())
+                    .map(|chunk_material| chunk_material.id())
+                    .collect();
+                for material_id in material_ids {
+                    chunk_materials.get_mut(material_id);
+                }
+            }
+            None => {
+                commands
+                    .entity(tilemap_layer_entity)
+                    .insert(TilesetAnimationData(images.add(image)));
+            }
+        }
+    }
+}
+
+/// Packs the animations of a layer's tilesets into a flat list of words, stored row by row in an
+/// `R32Uint` texture.
+///
+/// The first `MAX_TILESETS + 1` words hold the index of the first animation of each tileset,
+/// followed by the total number of animations. Then comes a header of three words for each
+/// animation: the offset of its first frame, its frame count and its total duration in
+/// milliseconds. Each frame is two words: its tileset index and the time in milliseconds at
+/// which it ends.
+fn make_animation_data_image<'a>(tilesets: impl Iterator<Item = &'a Tileset>) -> Image {
+    let animations: Vec<Vec<&TileAnimation>> = tilesets
+        .map(|tileset| tileset.animations.iter().collect())
+        .collect();
+    let animation_count: usize = animations.iter().map(Vec::len).sum();
+
+    let mut words = Vec::with_capacity(MAX_TILESETS + 1 + animation_count * 3);
+    let mut first_animation = 0;
+    for slot in 0..MAX_TILESETS {
+        words.push(first_animation);
+        first_animation += animations.get(slot).map_or(0, Vec::len) as u32;
+    }
+    words.push(animation_count as u32);
+
+    let mut frames = Vec::new();
+    let frames_start = (MAX_TILESETS + 1 + animation_count * 3) as u32;
+    for animation in animations.iter().flatten() {
+        words.push(frames_start + frames.len() as u32);
+        words.push(animation.frames.len() as u32);
+
+        let mut end_ms = 0;
+        for frame in &animation.frames {
+            end_ms += frame.duration.as_millis() as u32;
+            frames.extend([frame.tileset_index as u32, end_ms]);
+        }
+        words.push(end_ms);
+    }
+    words.extend(frames);
+
+    let height = (words.len() as u32).div_ceil(ANIMATION_DATA_WIDTH);
+    words.resize((ANIMATION_DATA_WIDTH * height) as usize, 0);
+
+    Image::new(
+        Extent3d {
+            width: ANIMATION_DATA_WIDTH,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bytemuck::cast_slice(&words).to_vec(),
+        TextureFormat::R32Uint,
+        RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+    )
+}
@@ -7,12 +7,23 @@ use bevy::{
     prelude::*,
     sprite::AlphaMode2d,
 };
+use tracing::warn;
 
+mod animation;
+#[cfg(feature = "picking")]
+mod picking_backend;
+mod render_mode;
+mod shared_tile_data;
 mod storage;
 mod tilemap_chunk;
 mod tilemap_chunk_material;
 mod tileset;
 
+pub use animation::*;
+#[cfg(feature = "picking")]
+pub use picking_backend::*;
+pub use render_mode::*;
+pub use shared_tile_data::*;
 pub use storage::*;
 pub use tilemap_chunk::*;
 pub use tilemap_chunk_material::*;
@@ -30,23 +41,39 @@ impl Plugin for TilemapPlugin {
             .init_asset_loader::<TilesetLoader>()
             .register_type::<TileOf>()
             .register_type::<TilemapTiles>()
-            .add_systems(PreUpdate, sync_tiles);
+            .register_type::<TileColor>()
+            .register_type::<TileVisible>()
+            .register_type::<TileFlip>()
+            .register_type::<TileAnimationIndex>()
+            .register_type::<TileTileset>()
+            .add_observer(on_insert_tile_of)
+            .add_observer(on_replace_tile_of)
+            .add_systems(PreUpdate, (move_tiles, sync_tiles).chain());
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
-pub enum TilemapRenderMode {
-    #[default]
-    Orthogonal,
-    Isometric,
-}
-
 #[derive(Component, Clone)]
-#[require(TileStorage, Tileset, Name::new("Tilemap"), Transform, Visibility)]
+#[require(
+    TileStorage,
+    TilemapTileEntities,
+    Tileset,
+    Name::new("Tilemap"),
+    Transform,
+    Visibility
+)]
 pub struct TilemapLayer {
     pub chunks: HashMap<IVec2, Entity>,
     pub alpha_mode: AlphaMode2d,
     pub render_mode: TilemapRenderMode,
+    /// Size of a single grid cell, separate from the tileset's texture `tile_size`.
+    ///
+    /// Tiles are drawn at their texture size and overhang their cell upwards, so e.g. 64x96
+    /// isometric tiles with a 64x32 diamond footprint use a grid size of 64x32. Where a tile sits
+    /// in its cell is given by [`TilemapRenderMode::tile_anchor`], which centers isometric and
+    /// staggered tiles horizontally on the cell, plus the tileset's
+    /// [`tile_offset`](Tileset::tile_offset).
+    /// Defaults to [`TilemapRenderMode::default_grid_size`] when `None`.
+    pub grid_size: Option<UVec2>,
     pub z_index: i32,
 }
 
@@ -56,18 +83,88 @@ impl Default for TilemapLayer {
             chunks: HashMap::new(),
             alpha_mode: AlphaMode2d::Blend,
             render_mode: TilemapRenderMode::default(),
+            grid_size: None,
             z_index: 0,
         }
     }
 }
 
 impl TilemapLayer {
+    /// Returns the grid cell size of this layer when drawn with `tileset`.
+    pub fn grid_size_for(&self, tileset: &Tileset) -> UVec2 {
+        self.grid_size
+            .unwrap_or_else(|| self.render_mode.default_grid_size(tileset.tile_size))
+    }
+
+    /// Returns the world position of the center of a tile's grid cell.
+    ///
+    /// `layer_transform` is the [`GlobalTransform`] of the tilemap layer entity.
+    pub fn tile_to_world(
+        &self,
+        layer_transform: &GlobalTransform,
+        tileset: &Tileset,
+        tile_position: IVec2,
+    ) -> Vec3 {
+        let local_position = self
+            .render_mode
+            .tile_center_to_local(tile_position, self.grid_size_for(tileset).as_vec2());
+        layer_transform.transform_point(local_position.extend(0.0))
+    }
+
+    /// Returns the position of the tile whose grid cell contains `world_position`.
+    ///
+    /// `layer_transform` is the [`GlobalTransform`] of the tilemap layer entity.
+    pub fn world_to_tile(
+        &self,
+        layer_transform: &GlobalTransform,
+        tileset: &Tileset,
+        world_position: Vec2,
+    ) -> IVec2 {
+        let local_position = layer_transform
+            .affine()
+            .inverse()
+            .transform_point3(world_position.extend(0.0))
+            .truncate();
+        self.render_mode
+            .local_to_tile(local_position, self.grid_size_for(tileset).as_vec2())
+    }
+
     pub fn isometric() -> Self {
         Self {
             render_mode: TilemapRenderMode::Isometric,
             ..default()
         }
     }
+
+    pub fn staggered(stagger: StaggerIndex) -> Self {
+        Self {
+            render_mode: TilemapRenderMode::Staggered { stagger },
+            ..default()
+        }
+    }
+
+    pub fn hexagonal(orientation: HexOrientation, stagger: StaggerIndex) -> Self {
+        Self {
+            render_mode: TilemapRenderMode::Hexagonal {
+                orientation,
+                stagger,
+            },
+            ..default()
+        }
+    }
+}
+
+/// The tile entities of a tilemap layer, by tile position.
+///
+/// Kept apart from [`TilemapLayer`] so that spawning and moving tiles doesn't change the layer.
+#[derive(Component, Clone, Debug, Default)]
+pub struct TilemapTileEntities(HashMap<IVec2, Entity>);
+
+impl TilemapTileEntities {
+    /// Returns the tile entity at `tile_position`, if there is one.
+    pub fn get(&self, tile_position: IVec2) -> Option<Entity> {
+        self.0.get(&tile_position).copied()
+    }
 }
 
 /// Stores all tiles in a tilemap.
@@ -88,10 +185,65 @@ pub struct Tile;
 #[reflect(Component)]
 pub struct TileTextureIndex(pub u16);
 
+/// Color a tile's texture is multiplied with.
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct TileColor(pub Color);
+
+impl Default for TileColor {
+    fn default() -> Self {
+        Self(Color::WHITE)
+    }
+}
+
+/// Whether a tile is rendered.
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct TileVisible(pub bool);
+
+impl Default for TileVisible {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Which of the layer's tilesets a tile is drawn from, see [`TileData::tileset`].
+#[derive(Component, Clone, Copy, Debug, Default, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct TileTileset(pub u8);
+
+/// Plays one of the [`Tileset::animations`] of the tile's tileset, replacing its
+/// [`TileTextureIndex`] for as long as it is present.
+#[derive(Component, Clone, Copy, Debug, Default, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+#[component(on_remove = on_remove_tile_animation_index)]
+pub struct TileAnimationIndex(pub u16);
+
+fn on_remove_tile_animation_index(
+    mut world: DeferredWorld,
+    HookContext { entity, .. }: HookContext,
+) {
+    world.commands().entity(entity).try_insert(TileDirty);
+}
+
+/// Flips a tile's texture, following Tiled's convention: the diagonal flip is applied first,
+/// followed by the horizontal and vertical flips. Together these cover all 8 orientations.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component)]
+pub struct TileFlip {
+    /// Flip horizontally.
+    pub x: bool,
+    /// Flip vertically.
+    pub y: bool,
+    /// Flip along the anti-diagonal, swapping the texture's x and y axes.
+    pub d: bool,
+}
+
 /// Position of a tile in the tilemap, in tile coordinates.
 #[derive(Component, Clone, Copy, Debug, Default, Deref, DerefMut, Reflect, PartialEq, Eq, Hash)]
 #[reflect(Component)]
 #[require(OldTilePosition)]
+#[component(on_remove = on_remove_tile_position)]
 pub struct TilePosition(pub IVec2);
 
 impl TilePosition {
@@ -105,43 +257,211 @@ impl TilePosition {
             TilePosition(self.0 - IVec2::X),
         ]
     }
+
+    /// Generates the six neighbors of this position in a hexagonal layout.
+    pub fn hex_neighbors(
+        &self,
+        orientation: HexOrientation,
+        stagger: StaggerIndex,
+    ) -> [TilePosition; 6] {
+        let axial = self.to_hex_axial(orientation, stagger);
+        [
+            IVec2::new(1, 0),
+            IVec2::new(1, -1),
+            IVec2::new(0, -1),
+            IVec2::new(-1, 0),
+            IVec2::new(-1, 1),
+            IVec2::new(0, 1),
+        ]
+        .map(|direction| TilePosition::from_hex_axial(axial + direction, orientation, stagger))
+    }
+
+    /// Converts this offset position in a hexagonal layout to axial coordinates.
+    pub fn to_hex_axial(&self, orientation: HexOrientation, stagger: StaggerIndex) -> IVec2 {
+        let IVec2 { x, y } = self.0;
+        match (orientation, stagger) {
+            (HexOrientation::PointyTop, StaggerIndex::Odd) => IVec2::new(x - (y - (y & 1)) / 2, y),
+            (HexOrientation::PointyTop, StaggerIndex::Even) => IVec2::new(x - (y + (y & 1)) / 2, y),
+            (HexOrientation::FlatTop, StaggerIndex::Odd) => IVec2::new(x, y - (x - (x & 1)) / 2),
+            (HexOrientation::FlatTop, StaggerIndex::Even) => IVec2::new(x, y - (x + (x & 1)) / 2),
+        }
+    }
+
+    /// Converts axial coordinates to an offset position in a hexagonal layout.
+    pub fn from_hex_axial(
+        axial: IVec2,
+        orientation: HexOrientation,
+        stagger: StaggerIndex,
+    ) -> Self {
+        let IVec2 { x: q, y: r } = axial;
+        TilePosition(match (orientation, stagger) {
+            (HexOrientation::PointyTop, StaggerIndex::Odd) => IVec2::new(q + (r - (r & 1)) / 2, r),
+            (HexOrientation::PointyTop, StaggerIndex::Even) => IVec2::new(q + (r + (r & 1)) / 2, r),
+            (HexOrientation::FlatTop, StaggerIndex::Odd) => IVec2::new(q, r + (q - (q & 1)) / 2),
+            (HexOrientation::FlatTop, StaggerIndex::Even) => IVec2::new(q, r + (q + (q & 1)) / 2),
+        })
+    }
+
+    /// Returns the number of steps between two positions in a hexagonal layout.
+    pub fn hex_distance(
+        &self,
+        other: &TilePosition,
+        orientation: HexOrientation,
+        stagger: StaggerIndex,
+    ) -> u32 {
+        let delta =
+            self.to_hex_axial(orientation, stagger) - other.to_hex_axial(orientation, stagger);
+        (delta.x.unsigned_abs() + (delta.x + delta.y).unsigned_abs() + delta.y.unsigned_abs()) / 2
+    }
 }
 
 /// When a tile is moved, we need to keep track of its old position so we can
-/// determine if it has moved to a new chunk.
+/// clear its old cell, which may be in a different chunk.
+/// Kept up to date automatically when [`TilePosition`] changes, until then it is the position of
+/// the tile's cell, which is cleared if the tile is despawned before the move is applied.
 #[derive(Component, Clone, Copy, Debug, Default, Deref, DerefMut, Reflect)]
 #[reflect(Component)]
 pub struct OldTilePosition(pub IVec2);
 
 /// Marker component for tiles that need to be re-rendered.
+///
+/// Changes to a tile's components are picked up automatically, this is only needed to force
+/// a tile to be written to its layer's [`TileStorage`] again.
 #[derive(Component, Clone, Debug, Default, Reflect)]
 #[reflect(Component)]
 pub struct TileDirty;
 
 /// Stores the tilemap entity that this tile belongs to.
+///
+/// Inserting a different `TileOf` moves the tile to that tilemap layer.
 #[derive(Component, Clone, Debug, Deref, DerefMut, Reflect)]
 #[require(
     Tile,
     Name = "Tile",
     TilePosition,
     TileTextureIndex,
+    TileTileset,
+    TileColor,
+    TileVisible,
+    TileFlip,
     // Transform,
     // Visibility
 )]
 #[relationship(relationship_target = TilemapTiles)]
 #[reflect(Component)]
-#[component(on_add = on_add_tile_of)]
 pub struct TileOf(pub Entity);
 
-fn on_add_tile_of(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
+// Relationships define their own insert and replace hooks, so `TileOf` is handled by observers
+// added by the `TilemapPlugin` instead, which also run when a tile is moved to another layer.
+
+fn on_insert_tile_of(trigger: Trigger<OnInsert, TileOf>, mut world: DeferredWorld) {
+    let entity = trigger.target();
     let tilemap_entity = world.get::<TileOf>(entity).unwrap().0;
     let tile_position = *world.get::<TilePosition>(entity).unwrap();
 
     world.commands().entity(entity).insert((
         Name::new(format!("Tile {},{}", tile_position.x, tile_position.y)),
         ChildOf(tilemap_entity),
-        TileDirty,
     ));
+
+    if let Some(mut old_tile_position) = world.get_mut::<OldTilePosition>(entity) {
+        old_tile_position.0 = tile_position.0;
+    }
+
+    if let Some(mut tile_entities) = world.get_mut::<TilemapTileEntities>(tilemap_entity) {
+        tile_entities.0.insert(tile_position.0, entity);
+    }
+}
+
+/// Clears a tile's cell in the layer it belonged to when its `TileOf` is replaced or removed,
+/// including when the tile is despawned.
+fn on_replace_tile_of(trigger: Trigger<OnReplace, TileOf>, mut world: DeferredWorld) {
+    let entity = trigger.target();
+    let tilemap_entity = world.get::<TileOf>(entity).unwrap().0;
+    let Some(old_tile_position) = world.get::<OldTilePosition>(entity).copied() else {
+        return;
+    };
+
+    // The tile's cell is where it was last synced, it may have moved since
+    remove_tile(&mut world, tilemap_entity, entity, old_tile_position.0);
+}
+
+fn on_remove_tile_position(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
+    let Some(tilemap_entity) = world.get::<TileOf>(entity).map(|tile_of| tile_of.0) else {
+        return;
+    };
+    let Some(old_tile_position) = world.get::<OldTilePosition>(entity).copied() else {
+        return;
+    };
+
+    remove_tile(&mut world, tilemap_entity, entity, old_tile_position.0);
+}
+
+/// Clears a tile entity's cell in its tilemap layer, unless another tile has taken its place.
+fn remove_tile(
+    world: &mut DeferredWorld,
+    tilemap_entity: Entity,
+    tile_entity: Entity,
+    tile_position: IVec2,
+) {
+    let Some(mut tile_entities) = world.get_mut::<TilemapTileEntities>(tilemap_entity) else {
+        return;
+    };
+    if tile_entities.get(tile_position) != Some(tile_entity) {
+        return;
+    }
+    tile_entities.0.remove(&tile_position);
+
+    if let Some(mut tile_storage) = world.get_mut::<TileStorage>(tilemap_entity) {
+        // The tile may have been outside the bounds of a dense storage, leaving nothing to clear
+        tile_storage.set(tile_position, None).ok();
+    }
+}
+
+type MovedTile = (
+    Entity,
+    &'static TileOf,
+    &'static TilePosition,
+    &'static mut OldTilePosition,
+    Option<&'static mut Name>,
+);
+
+fn move_tiles(
+    mut tiles_query: Query<MovedTile, (With<Tile>, Changed<TilePosition>)>,
+    mut tilemap_query: Query<(&mut TilemapTileEntities, &mut TileStorage)>,
+) {
+    for (tile_entity, tile_of, tile_position, mut old_tile_position, name) in &mut tiles_query {
+        if tile_position.0 == old_tile_position.0 {
+            continue;
+        }
+        let old_position = old_tile_position.0;
+        old_tile_position.0 = tile_position.0;
+
+        if let Some(mut name) = name {
+            name.set(format!("Tile {},{}", tile_position.x, tile_position.y));
+        }
+
+        let Ok((mut tile_entities, mut tile_storage)) = tilemap_query.get_mut(**tile_of) else {
+            continue;
+        };
+
+        // Only take the old cell's data if no other tile has moved into it since
+        let tile_data = if tile_entities.get(old_position) == Some(tile_entity) {
+            tile_entities.0.remove(&old_position);
+            let tile_data = tile_storage.get(old_position).ok().flatten().copied();
+            tile_storage.set(old_position, None).ok();
+            tile_data
+        } else {
+            None
+        };
+
+        tile_entities.0.insert(tile_position.0, tile_entity);
+        if tile_data.is_some() {
+            if let Err(err) = tile_storage.set(tile_position.0, tile_data) {
+                warn!("Unable to move tile {}: {}", tile_entity, err);
+            }
+        }
+    }
 }
 
 fn sync_tiles(
@@ -153,21 +473,64 @@ fn sync_tiles(
             &TileOf,
             &TilePosition,
             &TileTextureIndex,
+            &TileTileset,
+            &TileColor,
+            &TileVisible,
+            &TileFlip,
+            Option<&TileAnimationIndex>,
+            Has<TileDirty>,
+        ),
+        (
+            With<Tile>,
+            Or<(
+                With<TileDirty>,
+                Changed<TileOf>,
+                Changed<TilePosition>,
+                Changed<TileTextureIndex>,
+                Changed<TileTileset>,
+                Changed<TileColor>,
+                Changed<TileVisible>,
+                Changed<TileFlip>,
+                Changed<TileAnimationIndex>,
+            )>,
         ),
-        (With<Tile>, With<TileDirty>),
     >,
     mut tile_storage_query: Query<(&mut TileStorage, &Tileset)>,
 ) {
-    for (tile_entity, tile_of, tile_position, tile_texture_index) in &mut tiles_query {
-        commands.entity(tile_entity).remove::<TileDirty>();
+    for (
+        tile_entity,
+        tile_of,
+        tile_position,
+        tile_texture_index,
+        tile_tileset,
+        tile_color,
+        tile_visible,
+        tile_flip,
+        tile_animation_index,
+        tile_dirty,
+    ) in &mut tiles_query
+    {
+        if tile_dirty {
+            commands.entity(tile_entity).remove::<TileDirty>();
+        }
         let Ok((mut tile_storage, _tileset)) = tile_storage_query.get_mut(**tile_of) else {
             continue;
         };
 
-        tile_storage.set(
+        let result = tile_storage.set(
             tile_position.0,
-            Some(TileData::from_index(tile_texture_index.0)),
+            Some(TileData {
+                tileset_index: tile_animation_index.map_or(tile_texture_index.0, |index| index.0),
+                tileset: tile_tileset.0,
+                color: tile_color.0,
+                visible: tile_visible.0,
+                flip: *tile_flip,
+                animated: tile_animation_index.is_some(),
+            }),
         );
+        if let Err(err) = result {
+            warn!("Unable to sync tile {}: {}", tile_entity, err);
+        }
 
         // transform.translation = (tile_position.as_vec2() * tileset.tile_size.as_vec2()).extend(0.0);
     }
@@ -178,3 +541,442 @@ fn sync_tiles(
 pub mod prelude {
     pub use super::*;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX_LAYOUTS: [(HexOrientation, StaggerIndex); 4] = [
+        (HexOrientation::PointyTop, StaggerIndex::Odd),
+        (HexOrientation::PointyTop, StaggerIndex::Even),
+        (HexOrientation::FlatTop, StaggerIndex::Odd),
+        (HexOrientation::FlatTop, StaggerIndex::Even),
+    ];
+
+    fn sorted(positions: impl IntoIterator<Item = IVec2>) -> Vec<IVec2> {
+        let mut positions: Vec<IVec2> = positions.into_iter().collect();
+        positions.sort_by_key(|position| (position.x, position.y));
+        positions
+    }
+
+    #[test]
+    fn hex_neighbors_table() {
+        use HexOrientation::*;
+        use StaggerIndex::*;
+
+        let cases = [
+            (
+                PointyTop,
+                Odd,
+                (0, 0),
+                [(1, 0), (-1, 0), (-1, -1), (0, -1), (-1, 1), (0, 1)],
+            ),
+            (
+                PointyTop,
+                Odd,
+                (1, 1),
+                [(2, 1), (0, 1), (1, 0), (2, 0), (1, 2), (2, 2)],
+            ),
+            (
+                PointyTop,
+                Odd,
+                (0, -1),
+                [(1, -1), (-1, -1), (0, -2), (1, -2), (0, 0), (1, 0)],
+            ),
+            (
+                PointyTop,
+                Even,
+                (0, 0),
+                [(1, 0), (-1, 0), (0, -1), (1, -1), (0, 1), (1, 1)],
+            ),
+            (
+                PointyTop,
+                Even,
+                (0, -1),
+                [(1, -1), (-1, -1), (-1, -2), (0, -2), (-1, 0), (0, 0)],
+            ),
+            (
+                FlatTop,
+                Odd,
+                (0, 0),
+                [(0, 1), (0, -1), (-1, -1), (-1, 0), (1, -1), (1, 0)],
+            ),
+            (
+                FlatTop,
+                Odd,
+                (1, 0),
+                [(1, 1), (1, -1), (0, 0), (0, 1), (2, 0), (2, 1)],
+            ),
+            (
+                FlatTop,
+                Even,
+                (0, 0),
+                [(0, 1), (0, -1), (-1, 0), (-1, 1), (1, 0), (1, 1)],
+            ),
+            (
+                FlatTop,
+                Even,
+                (-2, -1),
+                [(-2, 0), (-2, -2), (-3, -1), (-3, 0), (-1, -1), (-1, 0)],
+            ),
+        ];
+
+        for (orientation, stagger, position, expected) in cases {
+            let neighbors = TilePosition(position.into()).hex_neighbors(orientation, stagger);
+            assert_eq!(
+                sorted(neighbors.map(|neighbor| neighbor.0)),
+                sorted(expected.map(IVec2::from)),
+                "{orientation:?} {stagger:?} {position:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hex_neighbors_are_adjacent_cells() {
+        for (orientation, stagger) in HEX_LAYOUTS {
+            let render_mode = TilemapRenderMode::Hexagonal {
+                orientation,
+                stagger,
+            };
+            // Regular hexagons, so all six neighbors are the same distance apart
+            let grid_size = match orientation {
+                HexOrientation::PointyTop => Vec2::new(3f32.sqrt(), 2.0),
+                HexOrientation::FlatTop => Vec2::new(2.0, 3f32.sqrt()),
+            };
+
+            for y in -4..=4 {
+                for x in -4..=4 {
+                    let position = IVec2::new(x, y);
+                    let center = render_mode.tile_to_local(position, grid_size);
+                    let adjacent = (-2..=2)
+                        .flat_map(|dy| (-2..=2).map(move |dx| position + IVec2::new(dx, dy)));
+                    let adjacent = adjacent.filter(|other| {
+                        let distance = render_mode
+                            .tile_to_local(*other, grid_size)
+                            .distance(center);
+                        distance > 0.0 && distance < 3f32.sqrt() * 1.01
+                    });
+
+                    let neighbors = TilePosition(position).hex_neighbors(orientation, stagger);
+                    assert_eq!(
+                        sorted(neighbors.map(|neighbor| neighbor.0)),
+                        sorted(adjacent),
+                        "{orientation:?} {stagger:?} {position}"
+                    );
+                    for neighbor in neighbors {
+                        assert_eq!(
+                            TilePosition(position).hex_distance(&neighbor, orientation, stagger),
+                            1
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_axial_round_trip() {
+        for (orientation, stagger) in HEX_LAYOUTS {
+            for y in -7..=7 {
+                for x in -7..=7 {
+                    let position = TilePosition(IVec2::new(x, y));
+                    let axial = position.to_hex_axial(orientation, stagger);
+                    assert_eq!(
+                        TilePosition::from_hex_axial(axial, orientation, stagger),
+                        position,
+                        "{orientation:?} {stagger:?}"
+                    );
+
+                    let position =
+                        TilePosition::from_hex_axial(IVec2::new(x, y), orientation, stagger);
+                    assert_eq!(
+                        position.to_hex_axial(orientation, stagger),
+                        IVec2::new(x, y),
+                        "{orientation:?} {stagger:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_axial_table() {
+        use HexOrientation::*;
+        use StaggerIndex::*;
+
+        let cases = [
+            (PointyTop, Odd, (3, -3), (5, -3)),
+            (PointyTop, Odd, (-2, -1), (-1, -1)),
+            (PointyTop, Even, (3, -3), (4, -3)),
+            (PointyTop, Even, (-2, -1), (-2, -1)),
+            (FlatTop, Odd, (-3, 3), (-3, 5)),
+            (FlatTop, Odd, (-1, -2), (-1, -1)),
+            (FlatTop, Even, (-3, 3), (-3, 4)),
+            (FlatTop, Even, (-1, -2), (-1, -2)),
+        ];
+
+        for (orientation, stagger, position, axial) in cases {
+            assert_eq!(
+                TilePosition(position.into()).to_hex_axial(orientation, stagger),
+                IVec2::from(axial),
+                "{orientation:?} {stagger:?} {position:?}"
+            );
+        }
+    }
+
+    /// A world running the tile systems of the [`TilemapPlugin`], with one tilemap layer.
+    fn tile_world() -> (World, Schedule, Entity) {
+        let mut world = World::new();
+        world.add_observer(on_insert_tile_of);
+        world.add_observer(on_replace_tile_of);
+        let tilemap_entity = world.spawn(TilemapLayer::default()).id();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((move_tiles, sync_tiles).chain());
+        (world, schedule, tilemap_entity)
+    }
+
+    fn stored_tile(world: &World, tilemap_entity: Entity, tile_position: IVec2) -> Option<u16> {
+        let tile_storage = world.get::<TileStorage>(tilemap_entity).unwrap();
+        let tile_data = tile_storage.get(tile_position).unwrap()?;
+        Some(tile_data.tileset_index)
+    }
+
+    fn layer_tile_entity(
+        world: &World,
+        tilemap_entity: Entity,
+        tile_position: IVec2,
+    ) -> Option<Entity> {
+        let tile_entities = world.get::<TilemapTileEntities>(tilemap_entity).unwrap();
+        tile_entities.get(tile_position)
+    }
+
+    fn spawn_tile(world: &mut World, tilemap_entity: Entity, tile_position: IVec2) -> Entity {
+        world
+            .spawn((
+                TileOf(tilemap_entity),
+                TilePosition(tile_position),
+                TileTextureIndex(7),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn despawned_tiles_are_cleared() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let tile_position = IVec2::new(1, 1);
+        let tile_entity = spawn_tile(&mut world, tilemap_entity, tile_position);
+        schedule.run(&mut world);
+        assert_eq!(stored_tile(&world, tilemap_entity, tile_position), Some(7));
+        assert_eq!(
+            layer_tile_entity(&world, tilemap_entity, tile_position),
+            Some(tile_entity)
+        );
+
+        world.despawn(tile_entity);
+        schedule.run(&mut world);
+        assert_eq!(stored_tile(&world, tilemap_entity, tile_position), None);
+        assert_eq!(
+            layer_tile_entity(&world, tilemap_entity, tile_position),
+            None
+        );
+    }
+
+    #[test]
+    fn removing_tile_components_clears_tiles() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let tile_of_entity = spawn_tile(&mut world, tilemap_entity, IVec2::new(1, 1));
+        let tile_position_entity = spawn_tile(&mut world, tilemap_entity, IVec2::new(2, 1));
+        schedule.run(&mut world);
+
+        world.entity_mut(tile_of_entity).remove::<TileOf>();
+        world
+            .entity_mut(tile_position_entity)
+            .remove::<TilePosition>();
+        schedule.run(&mut world);
+        for tile_position in [IVec2::new(1, 1), IVec2::new(2, 1)] {
+            assert_eq!(stored_tile(&world, tilemap_entity, tile_position), None);
+            assert_eq!(
+                layer_tile_entity(&world, tilemap_entity, tile_position),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn moved_tiles_change_chunks() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let tile_entity = spawn_tile(&mut world, tilemap_entity, IVec2::new(1, 1));
+        schedule.run(&mut world);
+        let mut tile_storage = world.get_mut::<TileStorage>(tilemap_entity).unwrap();
+        let chunk_positions = tile_storage.iter_dirty_chunk_positions().copied().collect();
+        tile_storage.clear_dirty_chunk_positions(chunk_positions);
+
+        let new_position = IVec2::new(40, 3);
+        world.get_mut::<TilePosition>(tile_entity).unwrap().0 = new_position;
+        schedule.run(&mut world);
+
+        assert_eq!(stored_tile(&world, tilemap_entity, IVec2::new(1, 1)), None);
+        assert_eq!(stored_tile(&world, tilemap_entity, new_position), Some(7));
+        assert_eq!(
+            layer_tile_entity(&world, tilemap_entity, new_position),
+            Some(tile_entity)
+        );
+        assert_eq!(
+            world.get::<OldTilePosition>(tile_entity).unwrap().0,
+            new_position
+        );
+
+        let tile_storage = world.get::<TileStorage>(tilemap_entity).unwrap();
+        let mut dirty_chunks: Vec<IVec2> =
+            tile_storage.iter_dirty_chunk_positions().copied().collect();
+        dirty_chunks.sort_by_key(|position| (position.x, position.y));
+        assert_eq!(dirty_chunks, [IVec2::new(0, 0), IVec2::new(1, 0)]);
+    }
+
+    #[test]
+    fn tiles_moved_then_despawned_are_cleared() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let tile_entity = spawn_tile(&mut world, tilemap_entity, IVec2::new(1, 1));
+        schedule.run(&mut world);
+
+        // Despawned before the move is synced
+        world.get_mut::<TilePosition>(tile_entity).unwrap().0 = IVec2::new(5, 5);
+        world.despawn(tile_entity);
+        schedule.run(&mut world);
+        for tile_position in [IVec2::new(1, 1), IVec2::new(5, 5)] {
+            assert_eq!(stored_tile(&world, tilemap_entity, tile_position), None);
+            assert_eq!(
+                layer_tile_entity(&world, tilemap_entity, tile_position),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn tiles_move_between_layers() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let other_tilemap_entity = world.spawn(TilemapLayer::default()).id();
+        let tile_entity = spawn_tile(&mut world, tilemap_entity, IVec2::new(1, 1));
+        schedule.run(&mut world);
+
+        // Moved to the other layer and to another position in the same frame
+        world
+            .entity_mut(tile_entity)
+            .insert((TileOf(other_tilemap_entity), TilePosition(IVec2::new(3, 2))));
+        schedule.run(&mut world);
+
+        assert_eq!(stored_tile(&world, tilemap_entity, IVec2::new(1, 1)), None);
+        assert_eq!(
+            layer_tile_entity(&world, tilemap_entity, IVec2::new(1, 1)),
+            None
+        );
+        assert_eq!(
+            stored_tile(&world, other_tilemap_entity, IVec2::new(3, 2)),
+            Some(7)
+        );
+        assert_eq!(
+            layer_tile_entity(&world, other_tilemap_entity, IVec2::new(3, 2)),
+            Some(tile_entity)
+        );
+        assert_eq!(
+            world.get::<ChildOf>(tile_entity).unwrap().parent(),
+            other_tilemap_entity
+        );
+    }
+
+    #[test]
+    fn changed_tile_components_are_synced() {
+        let (mut world, mut schedule, tilemap_entity) = tile_world();
+        let tile_position = IVec2::new(-2, 3);
+        let tile_entity = spawn_tile(&mut world, tilemap_entity, tile_position);
+        schedule.run(&mut world);
+
+        world.get_mut::<TileTextureIndex>(tile_entity).unwrap().0 = 9;
+        world.get_mut::<TileVisible>(tile_entity).unwrap().0 = false;
+        schedule.run(&mut world);
+        let tile_storage = world.get::<TileStorage>(tilemap_entity).unwrap();
+        let tile_data = tile_storage.get(tile_position).unwrap().unwrap();
+        assert_eq!(tile_data.tileset_index, 9);
+        assert!(!tile_data.visible);
+
+        world.entity_mut(tile_entity).insert(TileAnimationIndex(2));
+        schedule.run(&mut world);
+        let tile_storage = world.get::<TileStorage>(tilemap_entity).unwrap();
+        let tile_data = tile_storage.get(tile_position).unwrap().unwrap();
+        assert_eq!(tile_data.tileset_index, 2);
+        assert!(tile_data.animated);
+    }
+
+    #[test]
+    fn world_tile_round_trip() {
+        let render_modes = [
+            TilemapRenderMode::Orthogonal,
+            TilemapRenderMode::Isometric,
+            TilemapRenderMode::Staggered {
+                stagger: StaggerIndex::Odd,
+            },
+            TilemapRenderMode::Staggered {
+                stagger: StaggerIndex::Even,
+            },
+        ]
+        .into_iter()
+        .chain(
+            HEX_LAYOUTS.map(|(orientation, stagger)| TilemapRenderMode::Hexagonal {
+                orientation,
+                stagger,
+            }),
+        );
+        let tileset = Tileset {
+            tile_size: UVec2::splat(64),
+            ..default()
+        };
+        let grid_size = UVec2::new(40, 24);
+        let layer_transform = GlobalTransform::from(
+            Transform::from_xyz(-130.5, 77.0, 3.0)
+                .with_rotation(Quat::from_rotation_z(0.3))
+                .with_scale(Vec3::new(2.0, 1.5, 1.0)),
+        );
+
+        for render_mode in render_modes {
+            let tilemap_layer = TilemapLayer {
+                render_mode,
+                grid_size: Some(grid_size),
+                ..default()
+            };
+
+            for y in -6..=6 {
+                for x in -6..=6 {
+                    let tile_position = IVec2::new(x, y);
+                    let world_position =
+                        tilemap_layer.tile_to_world(&layer_transform, &tileset, tile_position);
+                    assert_eq!(world_position.z, 3.0);
+
+                    let local_center =
+                        render_mode.tile_center_to_local(tile_position, grid_size.as_vec2());
+                    assert!(
+                        layer_transform
+                            .affine()
+                            .inverse()
+                            .transform_point3(world_position)
+                            .abs_diff_eq(local_center.extend(0.0), 1e-3),
+                        "{render_mode:?} {tile_position}"
+                    );
+
+                    // Points near the center of the cell map back to the same tile
+                    for offset in [Vec2::ZERO, Vec2::new(0.2, 0.1), Vec2::new(-0.1, -0.2)] {
+                        let local_position = local_center + offset * grid_size.as_vec2();
+                        let world_position = layer_transform
+                            .transform_point(local_position.extend(0.0))
+                            .truncate();
+                        assert_eq!(
+                            tilemap_layer.world_to_tile(&layer_transform, &tileset, world_position),
+                            tile_position,
+                            "{render_mode:?} {tile_position} {offset}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::{
+    picking::backend::prelude::*, platform::collections::HashMap, prelude::*,
+    render::view::RenderLayers,
+};
+
+use super::{TileOf, TilePosition, TileStorage, TilemapLayer, TilemapTileEntities, Tileset};
+
+/// Plugin that adds a `bevy_picking` backend for tilemap layers.
+///
+/// Hits are resolved against each layer's [`TileStorage`], so tiles without an entity can be
+/// picked as well. A hit targets the tile's [`TileOf`] entity if there is one, or the layer
+/// entity otherwise. The topmost tile under each pointer is stored in [`TileHoverMap`].
+///
+/// Layers are only picked by cameras sharing one of their [`RenderLayers`], and hits are
+/// ordered by the layer's distance from the camera, then by its [`TilemapLayer::z_index`].
+#[derive(Default)]
+pub struct TilemapPickingPlugin;
+
+impl Plugin for TilemapPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TileHoverMap>()
+            .add_systems(PreUpdate, tilemap_picking.in_set(PickSet::Backend));
+    }
+}
+
+/// A tile under a pointer, as reported by the [`TilemapPickingPlugin`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileHit {
+    /// The tilemap layer entity containing the tile.
+    pub layer: Entity,
+    /// The tile's [`TileOf`] entity, if one exists.
+    pub tile: Option<Entity>,
+    /// The position of the tile in the layer, in tile coordinates.
+    pub tile_position: IVec2,
+    /// The position where the pointer hit the layer, in world space.
+    pub world_position: Vec3,
+}
+
+/// The topmost tile under each pointer, updated every frame by the [`TilemapPickingPlugin`].
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct TileHoverMap(HashMap<PointerId, TileHit>);
+
+/// Hits on layers in the same plane are moved this much closer to the camera for each step of
+/// [`TilemapLayer::z_index`], so that the layer drawn on top is hit first.
+const Z_INDEX_DEPTH_BIAS: f32 = 1e-3;
+
+type PickableTilemapLayer = (
+    Entity,
+    &'static TilemapLayer,
+    &'static TileStorage,
+    &'static TilemapTileEntities,
+    &'static Tileset,
+    &'static GlobalTransform,
+    &'static InheritedVisibility,
+    Option<&'static RenderLayers>,
+    Option<&'static Pickable>,
+);
+
+fn tilemap_picking(
+    ray_map: Res<RayMap>,
+    camera_query: Query<(&Camera, Option<&RenderLayers>)>,
+    tilemap_layer_query: Query<PickableTilemapLayer>,
+    tile_query: Query<(&TileOf, &TilePosition)>,
+    mut tile_hover_map: ResMut<TileHoverMap>,
+    mut output: EventWriter<PointerHits>,
+) {
+    let default_render_layers = RenderLayers::default();
+    let mut topmost_hits: HashMap<PointerId, (isize, TileHit)> = HashMap::new();
+
+    // Rays are only cast from cameras whose viewport and render target contain the pointer
+    for (&ray_id, ray) in ray_map.iter() {
+        let Ok((camera, camera_render_layers)) = camera_query.get(ray_id.camera) else {
+            continue;
+        };
+        if !camera.is_active {
+            continue;
+        }
+        let camera_render_layers = camera_render_layers.unwrap_or(&default_render_layers);
+
+        // Layers further along the ray are drawn behind closer ones, layers in the same plane
+        // are ordered by their z index
+        let mut layer_hits: Vec<_> = tilemap_layer_query
+            .iter()
+            .filter(|(.., visibility, render_layers, _)| {
+                visibility.get()
+                    && camera_render_layers
+                        .intersects(render_layers.unwrap_or(&default_render_layers))
+            })
+            .filter_map(|layer| {
+                let (_, tilemap_layer, _, _, _, transform, ..) = layer;
+                let distance = ray.intersect_plane(
+                    transform.translation(),
+                    InfinitePlane3d::new(transform.back()),
+                )?;
+                let depth = distance - tilemap_layer.z_index as f32 * Z_INDEX_DEPTH_BIAS;
+                Some((distance, depth, layer))
+            })
+            .collect();
+        layer_hits.sort_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+
+        let mut picks = Vec::new();
+        for (
+            distance,
+            depth,
+            (
+                tilemap_layer_entity,
+                tilemap_layer,
+                tile_storage,
+                tile_entities,
+                tileset,
+                transform,
+                ..,
+                pickable,
+            ),
+        ) in layer_hits
+        {
+            let world_position = ray.get_point(distance);
+            let local_position = transform
+                .affine()
+                .inverse()
+                .transform_point3(world_position)
+                .truncate();
+            let tile_position = tilemap_layer.render_mode.local_to_tile(
+                local_position,
+                tilemap_layer.grid_size_for(tileset).as_vec2(),
+            );
+
+            let Ok(Some(tile_data)) = tile_storage.get(tile_position) else {
+                continue;
+            };
+            if !tile_data.visible || tile_data.tileset_index == u16::MAX {
+                continue;
+            }
+
+            let tile = tile_entities.get(tile_position).filter(|tile_entity| {
+                tile_query
+                    .get(*tile_entity)
+                    .is_ok_and(|(tile_of, position)| {
+                        tile_of.0 == tilemap_layer_entity && position.0 == tile_position
+                    })
+            });
+
+            picks.push((
+                tile.unwrap_or(tilemap_layer_entity),
+                HitData::new(
+                    ray_id.camera,
+                    depth,
+                    Some(world_position),
+                    Some(*transform.back()),
+                ),
+            ));
+
+            if picks.len() == 1
+                && topmost_hits
+                    .get(&ray_id.pointer)
+                    .is_none_or(|(order, _)| camera.order > *order)
+            {
+                topmost_hits.insert(
+                    ray_id.pointer,
+                    (
+                        camera.order,
+                        TileHit {
+                            layer: tilemap_layer_entity,
+                            tile,
+                            tile_position,
+                            world_position,
+                        },
+                    ),
+                );
+            }
+
+            if pickable.is_none_or(|pickable| pickable.should_block_lower) {
+                break;
+            }
+        }
+
+        output.write(PointerHits::new(ray_id.pointer, picks, camera.order as f32));
+    }
+
+    tile_hover_map.clear();
+    tile_hover_map.extend(
+        topmost_hits
+            .into_iter()
+            .map(|(pointer, (_, tile_hit))| (pointer, tile_hit)),
+    );
+}
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+
+/// Determines how tiles in a [`TilemapLayer`](crate::TilemapLayer) are laid out in space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum TilemapRenderMode {
+    #[default]
+    Orthogonal,
+    Isometric,
+    /// Staggered ("zig-zag") isometric tiles. Every other row is offset by half a tile,
+    /// as selected by `stagger`, so the map covers a rectangular area.
+    Staggered {
+        stagger: StaggerIndex,
+    },
+    /// Hexagonal tiles. Every other row (pointy-top) or column (flat-top) is offset by
+    /// half a tile, as selected by `stagger`.
+    Hexagonal {
+        orientation: HexOrientation,
+        stagger: StaggerIndex,
+    },
+}
+
+/// The orientation of the hexagons in a [`TilemapRenderMode::Hexagonal`] layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum HexOrientation {
+    /// Hexagons have a vertex at the top. Rows are staggered horizontally.
+    #[default]
+    PointyTop,
+    /// Hexagons have an edge at the top. Columns are staggered vertically.
+    FlatTop,
+}
+
+/// Which rows or columns are offset by half a tile in staggered layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum StaggerIndex {
+    #[default]
+    Odd,
+    Even,
+}
+
+impl StaggerIndex {
+    /// Returns `true` if the row or column at `index` is offset.
+    pub fn is_staggered(&self, index: i32) -> bool {
+        match self {
+            StaggerIndex::Odd => index.rem_euclid(2) == 1,
+            StaggerIndex::Even => index.rem_euclid(2) == 0,
+        }
+    }
+}
+
+impl TilemapRenderMode {
+    /// Creates a pointy-top hexagonal render mode with rows offset according to `stagger`.
+    pub fn hex_pointy_top(stagger: StaggerIndex) -> Self {
+        Self::Hexagonal {
+            orientation: HexOrientation::PointyTop,
+            stagger,
+        }
+    }
+
+    /// Creates a flat-top hexagonal render mode with columns offset according to `stagger`.
+    pub fn hex_flat_top(stagger: StaggerIndex) -> Self {
+        Self::Hexagonal {
+            orientation: HexOrientation::FlatTop,
+            stagger,
+        }
+    }
+
+    /// Returns the grid cell size used when a layer doesn't specify one.
+    ///
+    /// Isometric layouts assume the diamond footprint fills the bottom half of the tile
+    /// texture, every other layout uses the tile texture size.
+    pub fn default_grid_size(&self, tile_size: UVec2) -> UVec2 {
+        match self {
+            TilemapRenderMode::Isometric | TilemapRenderMode::Staggered { .. } => {
+                UVec2::new(tile_size.x, tile_size.y / 2)
+            }
+            TilemapRenderMode::Orthogonal | TilemapRenderMode::Hexagonal { .. } => tile_size,
+        }
+    }
+
+    /// Returns the position of the bottom-left corner of a grid cell's tile quad, relative to
+    /// the bottom-left corner of the cell.
+    ///
+    /// Tiles larger than their grid cell overhang it upwards. In isometric and staggered
+    /// layouts they're also centered horizontally on the cell's diamond, like in Tiled.
+    pub fn tile_anchor(&self, tile_size: Vec2, grid_size: Vec2) -> Vec2 {
+        match self {
+            TilemapRenderMode::Isometric | TilemapRenderMode::Staggered { .. } => {
+                Vec2::new((grid_size.x - tile_size.x) * 0.5, 0.0)
+            }
+            TilemapRenderMode::Orthogonal | TilemapRenderMode::Hexagonal { .. } => Vec2::ZERO,
+        }
+    }
+
+    /// Returns the position of the bottom-left corner of a tile's grid cell, relative to
+    /// the origin of the tilemap layer.
+    ///
+    /// For isometric layouts `grid_size` is the size of the diamond footprint, for
+    /// hexagonal layouts it is the bounding box of a single hexagon.
+    pub fn tile_to_local(&self, tile_position: IVec2, grid_size: Vec2) -> Vec2 {
+        let tile = tile_position.as_vec2();
+        match *self {
+            TilemapRenderMode::Orthogonal => tile * grid_size,
+            TilemapRenderMode::Isometric => Vec2::new(
+                (tile.x - tile.y) * grid_size.x * 0.5,
+                (tile.x + tile.y) * grid_size.y * 0.5,
+            ),
+            TilemapRenderMode::Staggered { stagger } => {
+                let offset = if stagger.is_staggered(tile_position.y) {
+                    0.5
+                } else {
+                    0.0
+                };
+                Vec2::new((tile.x + offset) * grid_size.x, tile.y * grid_size.y * 0.5)
+            }
+            TilemapRenderMode::Hexagonal {
+                orientation: HexOrientation::PointyTop,
+                stagger,
+            } => {
+                let offset = if stagger.is_staggered(tile_position.y) {
+                    0.5
+                } else {
+                    0.0
+                };
+                Vec2::new((tile.x + offset) * grid_size.x, tile.y * grid_size.y * 0.75)
+            }
+            TilemapRenderMode::Hexagonal {
+                orientation: HexOrientation::FlatTop,
+                stagger,
+            } => {
+                let offset = if stagger.is_staggered(tile_position.x) {
+                    0.5
+                } else {
+                    0.0
+                };
+                Vec2::new(tile.x * grid_size.x * 0.75, (tile.y + offset) * grid_size.y)
+            }
+        }
+    }
+
+    /// Returns the position of the center of a tile's grid cell, relative to the origin of
+    /// the tilemap layer.
+    pub fn tile_center_to_local(&self, tile_position: IVec2, grid_size: Vec2) -> Vec2 {
+        self.tile_to_local(tile_position, grid_size) + grid_size * 0.5
+    }
+
+    /// Returns the tile whose grid cell contains `local_position`, relative to the origin
+    /// of the tilemap layer.
+    pub fn local_to_tile(&self, local_position: Vec2, grid_size: Vec2) -> IVec2 {
+        let half_grid_size = grid_size * 0.5;
+        let centered = local_position - half_grid_size;
+        match *self {
+            TilemapRenderMode::Orthogonal => (local_position / grid_size).floor().as_ivec2(),
+            TilemapRenderMode::Isometric => {
+                let diagonal = centered / half_grid_size;
+                IVec2::new(
+                    ((diagonal.x + diagonal.y) * 0.5).round() as i32,
+                    ((diagonal.y - diagonal.x) * 0.5).round() as i32,
+                )
+            }
+            TilemapRenderMode::Staggered { .. } | TilemapRenderMode::Hexagonal { .. } => {
+                let step = match *self {
+                    TilemapRenderMode::Hexagonal {
+                        orientation: HexOrientation::PointyTop,
+                        ..
+                    } => grid_size * Vec2::new(1.0, 0.75),
+                    TilemapRenderMode::Hexagonal {
+                        orientation: HexOrientation::FlatTop,
+                        ..
+                    } => grid_size * Vec2::new(0.75, 1.0),
+                    _ => grid_size * Vec2::new(1.0, 0.5),
+                };
+                // Ignoring the stagger puts the estimate at most one tile away in each
+                // direction, so pick the closest cell around it.
+                let estimate = (centered / step).round().as_ivec2();
+                let distance = |tile_position: IVec2| {
+                    let delta =
+                        local_position - self.tile_center_to_local(tile_position, grid_size);
+                    match self {
+                        // Diamonds contain every point within a unit "manhattan" distance
+                        TilemapRenderMode::Staggered { .. } => {
+                            (delta / half_grid_size).abs().element_sum()
+                        }
+                        _ => delta.length_squared(),
+                    }
+                };
+                (-1..=1)
+                    .flat_map(|y| (-1..=1).map(move |x| estimate + IVec2::new(x, y)))
+                    .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+                    .unwrap_or(estimate)
+            }
+        }
+    }
+
+    /// Reduces a chunk's origin tile to the smallest origin that produces the same chunk
+    /// mesh, so chunk meshes can be shared between chunks.
+    ///
+    /// Staggered layouts depend on the parity of the first row or column in the chunk,
+    /// all other layouts are independent of the chunk's position.
+    pub(crate) fn chunk_mesh_origin(&self, chunk_origin: IVec2) -> IVec2 {
+        match *self {
+            TilemapRenderMode::Orthogonal | TilemapRenderMode::Isometric => IVec2::ZERO,
+            TilemapRenderMode::Staggered { .. }
+            | TilemapRenderMode::Hexagonal {
+                orientation: HexOrientation::PointyTop,
+                ..
+            } => IVec2::new(0, chunk_origin.y.rem_euclid(2)),
+            TilemapRenderMode::Hexagonal {
+                orientation: HexOrientation::FlatTop,
+                ..
+            } => IVec2::new(chunk_origin.x.rem_euclid(2), 0),
+        }
+    }
+}
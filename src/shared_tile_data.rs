@@ -0,0 +1,105 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use super::TilemapChunkMaterial;
+
+/// Maximum width of a [`TilemapSharedTileData`] texture, in texels.
+const SHARED_TILE_DATA_MAX_WIDTH: u32 = 2048;
+
+/// Makes all chunks of a tilemap layer share a single material, with their tile data packed into
+/// one texture, so that chunks using the same mesh are batched into as few draw calls as possible.
+///
+/// Each chunk reads its tile data from its own slot in the texture, selected by the chunk's
+/// [`MeshTag`](bevy::render::mesh::MeshTag). The texture is at most 2048 texels wide and grows in
+/// height as chunks are added, so layers with more chunks than fit in the device's maximum
+/// texture size should keep the default of one material per chunk.
+///
+/// Insert this on the layer before its chunks are spawned.
+#[derive(Component, Debug, Default)]
+pub struct TilemapSharedTileData {
+    pub(crate) material: Handle<TilemapChunkMaterial>,
+    pub(crate) image: Handle<Image>,
+    pub(crate) chunk_size: UVec2,
+    slot_rows: u32,
+    slots: HashMap<IVec2, u32>,
+    free_slots: Vec<u32>,
+}
+
+impl TilemapSharedTileData {
+    /// Creates an empty shared tile data layout for chunks of `chunk_size`.
+    pub(crate) fn with_chunk_size(chunk_size: UVec2) -> Self {
+        Self {
+            chunk_size,
+            ..default()
+        }
+    }
+
+    fn slots_per_row(&self) -> u32 {
+        (SHARED_TILE_DATA_MAX_WIDTH / self.chunk_size.x.max(1)).max(1)
+    }
+
+    /// Returns the number of chunks the texture can hold.
+    pub(crate) fn capacity(&self) -> u32 {
+        self.slots_per_row() * self.slot_rows
+    }
+
+    /// Returns the size of the texture needed to hold all slots, in texels.
+    pub(crate) fn texture_size(&self) -> UVec2 {
+        UVec2::new(self.slots_per_row(), self.slot_rows) * self.chunk_size
+    }
+
+    /// Grows the texture layout to hold at least `chunk_count` chunks. Returns `true` if it grew,
+    /// in which case a new texture is needed.
+    pub(crate) fn reserve(&mut self, chunk_count: u32) -> bool {
+        if self.capacity() >= chunk_count {
+            return false;
+        }
+        let mut slot_rows = self.slot_rows.max(1);
+        while self.slots_per_row() * slot_rows < chunk_count {
+            slot_rows *= 2;
+        }
+        self.slot_rows = slot_rows;
+        true
+    }
+
+    /// Returns the slot of the chunk at `chunk_position`, if it has one.
+    pub(crate) fn slot(&self, chunk_position: IVec2) -> Option<u32> {
+        self.slots.get(&chunk_position).copied()
+    }
+
+    /// Returns the slot of the chunk at `chunk_position`, assigning it a free one if it has none.
+    /// Returns `None` if the texture is full.
+    ///
+    /// Also returns whether the slot was freed by another chunk, in which case it still holds
+    /// that chunk's tiles.
+    pub(crate) fn allocate_slot(&mut self, chunk_position: IVec2) -> Option<(u32, bool)> {
+        if let Some(slot) = self.slots.get(&chunk_position) {
+            return Some((*slot, false));
+        }
+        // Freed slots are always reused first, so otherwise all slots below `len` are taken
+        let (slot, reused) = match self.free_slots.pop() {
+            Some(slot) => (slot, true),
+            None if (self.slots.len() as u32) < self.capacity() => (self.slots.len() as u32, false),
+            None => return None,
+        };
+        self.slots.insert(chunk_position, slot);
+        Some((slot, reused))
+    }
+
+    /// Frees the slots of all chunks for which `keep` returns `false`.
+    pub(crate) fn retain_slots(&mut self, mut keep: impl FnMut(IVec2) -> bool) {
+        let free_slots = &mut self.free_slots;
+        self.slots.retain(|chunk_position, slot| {
+            let retain = keep(*chunk_position);
+            if !retain {
+                free_slots.push(*slot);
+            }
+            retain
+        });
+    }
+
+    /// Returns the texel at which the tile data of `slot` starts.
+    pub(crate) fn slot_origin(&self, slot: u32) -> UVec2 {
+        let slots_per_row = self.slots_per_row();
+        UVec2::new(slot % slots_per_row, slot / slots_per_row) * self.chunk_size
+    }
+}
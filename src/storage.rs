@@ -3,20 +3,35 @@ use bevy::{
     prelude::*,
 };
 use derive_more::derive::AsRef;
+use thiserror::Error;
+
+use super::{MAX_TILESETS, TileFlip};
 
 #[derive(Clone, Copy, Debug, AsRef)]
 pub struct TileData {
+    /// Index of the tile's texture in the tileset, or of its animation if `animated` is set.
     pub tileset_index: u16,
+    /// Which of the layer's tilesets the tile is drawn from, `0` being the layer's
+    /// [`Tileset`](crate::Tileset) and `1..` its [`AdditionalTilesets`](crate::AdditionalTilesets).
+    /// Must be less than [`MAX_TILESETS`], [`TileStorage`] rejects tiles with other tilesets.
+    pub tileset: u8,
     pub color: Color,
     pub visible: bool,
+    pub flip: TileFlip,
+    /// Whether `tileset_index` refers to one of the layer's [`Tileset::animations`](crate::Tileset::animations).
+    #[as_ref(skip)]
+    pub animated: bool,
 }
 
 impl Default for TileData {
     fn default() -> Self {
         Self {
             tileset_index: u16::MAX,
+            tileset: 0,
             color: Color::WHITE,
             visible: true,
+            flip: TileFlip::default(),
+            animated: false,
         }
     }
 }
@@ -28,17 +43,54 @@ impl TileData {
             ..default()
         }
     }
+
+    pub fn from_animation(animation_index: u16) -> Self {
+        Self {
+            tileset_index: animation_index,
+            animated: true,
+            ..default()
+        }
+    }
+
+    /// Returns an error if `tileset` doesn't refer to one of the [`MAX_TILESETS`] tilesets a
+    /// layer can draw from.
+    fn check_tileset(&self) -> Result<(), TileStorageError> {
+        if self.tileset as usize >= MAX_TILESETS {
+            return Err(TileStorageError::InvalidTileset {
+                tileset: self.tileset,
+            });
+        }
+        Ok(())
+    }
 }
 
+/// Errors that can occur when accessing a [`TileStorage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
 pub enum TileStorageError {
+    /// The tile position is outside the bounds of a dense storage.
+    #[error("tile position ({x}, {y}) is out of bounds")]
     OutOfBounds { x: i32, y: i32 },
+    /// A chunk size with a zero dimension was given.
+    #[error("invalid chunk size ({x}, {y}), both dimensions must be non-zero")]
+    InvalidChunkSize { x: u32, y: u32 },
+    /// A tile referenced a tileset past the [`MAX_TILESETS`] a layer can draw from.
+    #[error(
+        "invalid tileset {tileset}, layers draw from at most {} tilesets",
+        MAX_TILESETS
+    )]
+    InvalidTileset { tileset: u8 },
 }
 
 #[derive(Component)]
 pub struct TileStorage {
     data: TileStorageData,
     chunk_size: IVec2,
-    dirty_chunk_positions: HashSet<IVec2>,
+    /// The chunks whose tiles changed since they were last cleared, with the bounding rect of
+    /// the changed tiles.
+    dirty_chunks: HashMap<IVec2, IRect>,
+    /// The number of tiles in each non-empty chunk of a sparse or dense storage, so finding empty
+    /// chunks doesn't need to scan their tiles. Chunked storage counts the tiles of each page.
+    chunk_tile_counts: HashMap<IVec2, u32>,
 }
 
 impl Default for TileStorage {
@@ -46,7 +98,8 @@ impl Default for TileStorage {
         Self {
             data: TileStorageData::Sparse(HashMap::new()),
             chunk_size: IVec2::splat(32),
-            dirty_chunk_positions: HashSet::new(),
+            dirty_chunks: HashMap::new(),
+            chunk_tile_counts: HashMap::new(),
         }
     }
 }
@@ -63,175 +116,493 @@ impl TileStorage {
         }
     }
 
+    /// Creates a storage that allocates one dense page of tiles for each chunk holding at least
+    /// one tile, and frees it again once the chunk is empty.
+    ///
+    /// This suits large or unbounded maps better than [`TileStorage::sparse`], as each tile
+    /// only costs its [`TileData`] and chunks are read as contiguous slices.
+    pub fn chunked() -> Self {
+        Self {
+            data: TileStorageData::Chunked(HashMap::new()),
+            ..default()
+        }
+    }
+
+    /// Creates a dense storage holding `size` tiles, starting at tile position `(0, 0)`.
     pub fn dense(size: UVec2) -> Self {
+        Self::dense_rect(IRect::from_corners(IVec2::ZERO, size.as_ivec2()))
+    }
+
+    /// Creates a dense storage holding the tiles in `bounds`, which may start at a negative
+    /// tile position, e.g. for maps centered on the origin.
+    ///
+    /// `bounds.max` is exclusive.
+    pub fn dense_rect(bounds: IRect) -> Self {
+        let size = bounds.size().max(IVec2::ZERO).as_uvec2();
         Self {
             data: TileStorageData::Dense {
                 tiles: vec![None; size.x as usize * size.y as usize],
-                size,
+                bounds,
             },
             ..default()
         }
     }
 
-    pub fn set_chunk_size(&mut self, chunk_size: UVec2) {
-        self.chunk_size = chunk_size.as_ivec2();
+    /// Returns the tile positions a dense storage can hold, or `None` for sparse and chunked
+    /// storage.
+    pub fn bounds(&self) -> Option<IRect> {
+        match &self.data {
+            TileStorageData::Sparse(_) | TileStorageData::Chunked(_) => None,
+            TileStorageData::Dense { bounds, .. } => Some(*bounds),
+        }
+    }
+
+    /// Changes the size of the chunks the tiles are rendered in.
+    ///
+    /// Changing the chunk size of a layer at runtime marks every chunk as dirty, and the layer's
+    /// existing chunk entities are replaced by ones with the new layout.
+    pub fn set_chunk_size(&mut self, chunk_size: UVec2) -> Result<(), TileStorageError> {
+        if chunk_size.cmpeq(UVec2::ZERO).any() {
+            return Err(TileStorageError::InvalidChunkSize {
+                x: chunk_size.x,
+                y: chunk_size.y,
+            });
+        }
+        let chunk_size = chunk_size.as_ivec2();
+        if let TileStorageData::Chunked(chunks) = &mut self.data {
+            let old_chunk_size = self.chunk_size;
+            let mut rechunked = HashMap::new();
+            for (chunk_position, chunk) in chunks.drain() {
+                let chunk_min = chunk_position * old_chunk_size;
+                for (index, tile_data) in chunk.tiles.iter().enumerate() {
+                    let Some(tile_data) = tile_data else {
+                        continue;
+                    };
+                    let local = IVec2::new(
+                        index as i32 % old_chunk_size.x,
+                        index as i32 / old_chunk_size.x,
+                    );
+                    let tile_position = chunk_min + local;
+                    let new_chunk_position = tile_position.div_euclid(chunk_size);
+                    rechunked
+                        .entry(new_chunk_position)
+                        .or_insert_with(|| TileChunkPage::new(chunk_size))
+                        .set(
+                            chunk_page_index(
+                                chunk_size,
+                                tile_position - new_chunk_position * chunk_size,
+                            ),
+                            Some(*tile_data),
+                        );
+                }
+            }
+            *chunks = rechunked;
+        }
+        if chunk_size != self.chunk_size {
+            self.chunk_size = chunk_size;
+            // Dirty positions refer to the old chunks, dirty every chunk of the new layout instead
+            self.dirty_chunks.clear();
+            self.set_all_dirty();
+            self.count_chunk_tiles();
+        }
+        Ok(())
     }
 
     pub fn chunk_size(&self) -> UVec2 {
         self.chunk_size.as_uvec2()
     }
 
+    /// Returns the position of the chunk containing `tile_position`.
+    pub fn chunk_position(&self, tile_position: IVec2) -> IVec2 {
+        tile_position.div_euclid(self.chunk_size)
+    }
+
     pub fn get(&self, tile_position: IVec2) -> Result<Option<&TileData>, TileStorageError> {
         match &self.data {
             TileStorageData::Sparse(tiles) => Ok(tiles.get(&tile_position)),
-            TileStorageData::Dense { tiles, size } => {
-                let Some(tile) = tiles
-                    .get(tile_position.x as usize + tile_position.y as usize * size.x as usize)
-                else {
+            TileStorageData::Dense { tiles, bounds } => {
+                let Some(index) = dense_index(bounds, tile_position) else {
                     return Err(TileStorageError::OutOfBounds {
                         x: tile_position.x,
                         y: tile_position.y,
                     });
                 };
-                Ok(tile.as_ref())
+                Ok(tiles[index].as_ref())
+            }
+            TileStorageData::Chunked(chunks) => {
+                let chunk_position = self.chunk_position(tile_position);
+                Ok(chunks.get(&chunk_position).and_then(|chunk| {
+                    chunk.tiles[chunk_page_index(
+                        self.chunk_size,
+                        tile_position - chunk_position * self.chunk_size,
+                    )]
+                    .as_ref()
+                }))
             }
         }
     }
 
-    pub fn set(&mut self, tile_position: IVec2, tile_data: Option<TileData>) {
-        match &mut self.data {
+    pub fn set(
+        &mut self,
+        tile_position: IVec2,
+        tile_data: Option<TileData>,
+    ) -> Result<(), TileStorageError> {
+        if let Some(tile_data) = &tile_data {
+            tile_data.check_tileset()?;
+        }
+        let chunk_position = self.chunk_position(tile_position);
+        let had_tile = match &mut self.data {
             TileStorageData::Sparse(tiles) => {
                 if let Some(tile_data) = tile_data {
-                    tiles.insert(tile_position, tile_data);
+                    tiles.insert(tile_position, tile_data).is_some()
                 } else {
-                    tiles.remove(&tile_position);
+                    tiles.remove(&tile_position).is_some()
                 }
             }
-            TileStorageData::Dense { tiles, size } => {
-                let Some(tile) = tiles
-                    .get_mut(tile_position.x as usize + tile_position.y as usize * size.x as usize)
-                else {
-                    return;
+            TileStorageData::Dense { tiles, bounds } => {
+                let Some(index) = dense_index(bounds, tile_position) else {
+                    return Err(TileStorageError::OutOfBounds {
+                        x: tile_position.x,
+                        y: tile_position.y,
+                    });
                 };
-                *tile = tile_data;
+                core::mem::replace(&mut tiles[index], tile_data).is_some()
+            }
+            TileStorageData::Chunked(chunks) => {
+                let index = chunk_page_index(
+                    self.chunk_size,
+                    tile_position - chunk_position * self.chunk_size,
+                );
+                if tile_data.is_some() {
+                    chunks
+                        .entry(chunk_position)
+                        .or_insert_with(|| TileChunkPage::new(self.chunk_size))
+                        .set(index, tile_data);
+                } else if let Some(chunk) = chunks.get_mut(&chunk_position) {
+                    chunk.set(index, None);
+                    if chunk.is_empty() {
+                        chunks.remove(&chunk_position);
+                    }
+                }
+                // Pages count their own tiles, leave `chunk_tile_counts` alone
+                tile_data.is_some()
+            }
+        };
+
+        match (had_tile, tile_data.is_some()) {
+            (false, true) => *self.chunk_tile_counts.entry(chunk_position).or_default() += 1,
+            (true, false) => {
+                if let Some(count) = self.chunk_tile_counts.get_mut(&chunk_position) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.chunk_tile_counts.remove(&chunk_position);
+                    }
+                }
             }
+            _ => {}
         }
 
-        self.dirty_chunk_positions
-            .insert(tile_position.div_euclid(self.chunk_size));
+        let tile_rect = IRect::from_corners(tile_position, tile_position + IVec2::ONE);
+        self.dirty_chunks
+            .entry(chunk_position)
+            .and_modify(|dirty_rect| *dirty_rect = dirty_rect.union(tile_rect))
+            .or_insert(tile_rect);
+        Ok(())
     }
 
-    pub fn fill_rect_with<F>(&mut self, rect: IRect, mut f: F)
+    /// Sets every tile in `rect` to the result of `f`. Nothing is written if `rect` isn't
+    /// entirely within the bounds of a dense storage.
+    ///
+    /// Tiles with an invalid [`TileData::tileset`] are skipped, and the first of them is returned
+    /// as an error once the rest of `rect` has been filled.
+    pub fn fill_rect_with<F>(&mut self, rect: IRect, mut f: F) -> Result<(), TileStorageError>
     where
         F: FnMut(IVec2) -> Option<TileData>,
     {
+        self.check_rect(rect)?;
+        let mut result = Ok(());
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
-                self.set(IVec2::new(x, y), f(IVec2::new(x, y)));
+                let tile_result = self.set(IVec2::new(x, y), f(IVec2::new(x, y)));
+                result = result.and(tile_result);
             }
         }
+        result
     }
 
-    pub fn fill_rect(&mut self, rect: IRect, tile_data: Option<TileData>) {
-        self.fill_rect_with(rect, |_| tile_data);
+    pub fn fill_rect(
+        &mut self,
+        rect: IRect,
+        tile_data: Option<TileData>,
+    ) -> Result<(), TileStorageError> {
+        self.fill_rect_with(rect, |_| tile_data)
     }
 
-    pub fn fill(&mut self, tile_data: Option<TileData>) {
-        match &mut self.data {
-            TileStorageData::Sparse(_) => {
-                warn!("TileStorage::fill is not supported for sparse tile storage");
-            }
-            TileStorageData::Dense { tiles, .. } => {
-                tiles.fill(tile_data);
-                self.set_all_dirty();
-            }
+    /// Sets every tile of a dense storage to `tile_data`.
+    ///
+    /// Sparse and chunked storage have no bounds, so only their occupied tiles are set, or
+    /// removed if `tile_data` is `None`. Use [`TileStorage::fill_rect`] to fill an explicit area
+    /// with any storage.
+    pub fn fill(&mut self, tile_data: Option<TileData>) -> Result<(), TileStorageError> {
+        if let Some(tile_data) = &tile_data {
+            tile_data.check_tileset()?;
         }
+        self.fill_with(|| tile_data)
     }
 
-    pub fn fill_with<F>(&mut self, f: F)
+    /// Sets every tile of a dense storage, or every occupied tile of a sparse or chunked
+    /// storage, to the result of `f`. See [`TileStorage::fill`].
+    ///
+    /// Tiles for which `f` returns an invalid [`TileData::tileset`] are skipped, and the first of
+    /// them is returned as an error once the rest of the storage has been filled.
+    pub fn fill_with<F>(&mut self, mut f: F) -> Result<(), TileStorageError>
     where
         F: FnMut() -> Option<TileData>,
     {
+        // Dirty the chunks before filling so chunks that end up empty are still updated
+        self.set_all_dirty();
+
+        let mut result = Ok(());
+        let mut f = || {
+            let tile_data = f();
+            if let Some(Err(err)) = tile_data.as_ref().map(TileData::check_tileset) {
+                result = result.and(Err(err));
+                return None;
+            }
+            Some(tile_data)
+        };
         match &mut self.data {
-            TileStorageData::Sparse(_) => {
-                warn!("TileStorage::fill_with is not supported for sparse tile storage");
+            TileStorageData::Sparse(tiles) => {
+                tiles.retain(|_, tile| match f() {
+                    Some(Some(tile_data)) => {
+                        *tile = tile_data;
+                        true
+                    }
+                    Some(None) => false,
+                    None => true,
+                });
             }
             TileStorageData::Dense { tiles, .. } => {
-                tiles.fill_with(f);
-                self.set_all_dirty();
+                for tile in tiles {
+                    if let Some(tile_data) = f() {
+                        *tile = tile_data;
+                    }
+                }
+            }
+            TileStorageData::Chunked(chunks) => {
+                chunks.retain(|_, chunk| {
+                    for index in 0..chunk.tiles.len() {
+                        if chunk.tiles[index].is_some() {
+                            if let Some(tile_data) = f() {
+                                chunk.set(index, tile_data);
+                            }
+                        }
+                    }
+                    !chunk.is_empty()
+                });
             }
         }
+
+        self.count_chunk_tiles();
+        result
     }
 
-    pub fn set_all_dirty(&mut self) {
-        match &mut self.data {
-            TileStorageData::Sparse(_) => {
-                warn!("TileStorage::set_all_dirty is not supported for sparse tile storage");
-            }
-            TileStorageData::Dense { size, .. } => {
-                let size_in_chunks = size.as_ivec2().div_euclid(self.chunk_size);
-                for y in 0..size_in_chunks.y {
-                    for x in 0..size_in_chunks.x {
-                        self.dirty_chunk_positions.insert(IVec2::new(x, y));
+    /// Recounts the tiles in each chunk of a sparse or dense storage after bulk changes.
+    fn count_chunk_tiles(&mut self) {
+        let chunk_size = self.chunk_size;
+        let mut chunk_tile_counts = HashMap::new();
+        let mut count_tile = |tile_position: IVec2| {
+            *chunk_tile_counts
+                .entry(tile_position.div_euclid(chunk_size))
+                .or_default() += 1;
+        };
+        match &self.data {
+            TileStorageData::Sparse(tiles) => tiles.keys().copied().for_each(count_tile),
+            TileStorageData::Dense { tiles, bounds } => {
+                let width = bounds.width().max(1) as usize;
+                for (index, tile) in tiles.iter().enumerate() {
+                    if tile.is_some() {
+                        let offset = IVec2::new((index % width) as i32, (index / width) as i32);
+                        count_tile(bounds.min + offset);
                     }
                 }
             }
+            TileStorageData::Chunked(_) => {}
+        }
+        self.chunk_tile_counts = chunk_tile_counts;
+    }
+
+    /// Marks every chunk of a dense storage, or every chunk holding at least one tile in a sparse
+    /// or chunked storage, as dirty.
+    pub fn set_all_dirty(&mut self) {
+        let chunk_positions: HashSet<IVec2> = match &self.data {
+            TileStorageData::Sparse(tiles) => tiles
+                .keys()
+                .map(|tile_position| self.chunk_position(*tile_position))
+                .collect(),
+            TileStorageData::Dense { bounds, .. } => {
+                if bounds.is_empty() {
+                    return;
+                }
+                let min_chunk = self.chunk_position(bounds.min);
+                let max_chunk = self.chunk_position(bounds.max - IVec2::ONE);
+                (min_chunk.y..=max_chunk.y)
+                    .flat_map(|y| (min_chunk.x..=max_chunk.x).map(move |x| IVec2::new(x, y)))
+                    .collect()
+            }
+            TileStorageData::Chunked(chunks) => chunks.keys().copied().collect(),
+        };
+
+        for chunk_position in chunk_positions {
+            let chunk_rect = self.chunk_rect(chunk_position);
+            self.dirty_chunks.insert(chunk_position, chunk_rect);
+        }
+    }
+
+    /// Returns the tile positions covered by the chunk at `chunk_position`. `max` is exclusive.
+    pub fn chunk_rect(&self, chunk_position: IVec2) -> IRect {
+        IRect::from_corners(
+            chunk_position * self.chunk_size,
+            (chunk_position + IVec2::ONE) * self.chunk_size,
+        )
+    }
+
+    /// Returns `true` if the chunk at `chunk_position` doesn't contain any tiles.
+    ///
+    /// Tiles are counted as they're set, so this doesn't need to look at the chunk's tiles.
+    pub fn is_chunk_empty(&self, chunk_position: IVec2) -> bool {
+        match &self.data {
+            TileStorageData::Chunked(chunks) => !chunks.contains_key(&chunk_position),
+            TileStorageData::Sparse(_) | TileStorageData::Dense { .. } => {
+                !self.chunk_tile_counts.contains_key(&chunk_position)
+            }
         }
     }
 
     pub fn clear_dirty_chunk_positions(&mut self, chunk_positions: HashSet<IVec2>) {
-        self.dirty_chunk_positions
-            .retain(|chunk_position| !chunk_positions.contains(chunk_position));
+        self.dirty_chunks
+            .retain(|chunk_position, _| !chunk_positions.contains(chunk_position));
     }
 
     pub fn iter_dirty_chunk_positions(&self) -> impl Iterator<Item = &IVec2> {
-        self.dirty_chunk_positions.iter()
+        self.dirty_chunks.keys()
+    }
+
+    /// Returns the bounding rect of the tiles that changed in the chunk at `chunk_position` since
+    /// it was last cleared, or `None` if the chunk isn't dirty.
+    pub fn dirty_chunk_rect(&self, chunk_position: IVec2) -> Option<IRect> {
+        self.dirty_chunks.get(&chunk_position).copied()
     }
 
+    /// Iterates the tiles in `rect` row by row, bottom to top.
+    ///
+    /// Returns an error if `rect` isn't entirely within the bounds of a dense storage.
     pub fn iter_sub_rect(
         &self,
         rect: IRect,
     ) -> Result<impl Iterator<Item = Option<&TileData>>, TileStorageError> {
+        self.check_rect(rect)?;
+        Ok(self.sub_rect_tiles(rect).into_iter())
+    }
+
+    /// Collects the tiles in `rect` row by row, with `None` for positions outside the bounds of a
+    /// dense storage.
+    pub(crate) fn sub_rect_tiles(&self, rect: IRect) -> Vec<Option<&TileData>> {
         let IRect { min, max } = rect;
+        let mut result =
+            Vec::with_capacity(rect.size().max(IVec2::ZERO).element_product() as usize);
         match &self.data {
             TileStorageData::Sparse(tiles) => {
-                let mut result = Vec::with_capacity(rect.size().element_product() as usize);
                 for y in min.y..max.y {
                     for x in min.x..max.x {
                         result.push(tiles.get(&IVec2::new(x, y)));
                     }
                 }
-                Ok(result.into_iter())
             }
-            TileStorageData::Dense { tiles, size } => {
-                if min.x < 0 || min.y < 0 || max.x >= size.x as i32 || max.y >= size.y as i32 {
-                    return Err(TileStorageError::OutOfBounds { x: min.x, y: min.y });
+            TileStorageData::Dense { tiles, bounds } => {
+                let width = rect.width().max(0) as usize;
+                let inner = rect.intersect(*bounds);
+                let stride = bounds.width() as usize;
+                for y in min.y..max.y {
+                    if inner.is_empty() || y < inner.min.y || y >= inner.max.y {
+                        result.extend(core::iter::repeat_n(None, width));
+                        continue;
+                    }
+                    let offset = (IVec2::new(inner.min.x, y) - bounds.min).as_uvec2();
+                    let start = offset.y as usize * stride + offset.x as usize;
+                    let end = start + inner.width() as usize;
+                    result.extend(core::iter::repeat_n(None, (inner.min.x - min.x) as usize));
+                    result.extend(tiles[start..end].iter().map(Option::as_ref));
+                    result.extend(core::iter::repeat_n(None, (max.x - inner.max.x) as usize));
+                }
+            }
+            TileStorageData::Chunked(chunks) => {
+                let chunk_size = self.chunk_size;
+                for y in min.y..max.y {
+                    // Copy each row one chunk-wide slice at a time
+                    let mut x = min.x;
+                    while x < max.x {
+                        let chunk_position = IVec2::new(x, y).div_euclid(chunk_size);
+                        let chunk_min = chunk_position * chunk_size;
+                        let end = max.x.min(chunk_min.x + chunk_size.x);
+                        let len = (end - x) as usize;
+                        match chunks.get(&chunk_position) {
+                            Some(chunk) => {
+                                let start =
+                                    chunk_page_index(chunk_size, IVec2::new(x, y) - chunk_min);
+                                result.extend(
+                                    chunk.tiles[start..start + len].iter().map(Option::as_ref),
+                                );
+                            }
+                            None => result.extend(core::iter::repeat_n(None, len)),
+                        }
+                        x = end;
+                    }
                 }
-                let rect: URect = rect.as_urect();
-                let width = rect.size().x as usize;
-                Ok((rect.min.y..rect.max.y)
-                    .flat_map(move |row| {
-                        let start = row as usize * size.x as usize + rect.min.x as usize;
-                        let end = start + width;
-                        tiles[start..end].iter().map(|opt| opt.as_ref())
-                    })
-                    .collect::<Vec<_>>()
-                    .into_iter())
             }
         }
+        result
+    }
+
+    /// Returns the page of the chunk at `chunk_position` if the storage is chunked and the chunk
+    /// holds any tiles.
+    pub fn chunk_page(&self, chunk_position: IVec2) -> Option<&TileChunkPage> {
+        match &self.data {
+            TileStorageData::Chunked(chunks) => chunks.get(&chunk_position),
+            _ => None,
+        }
+    }
+
+    /// Returns an error if `rect` isn't entirely within the bounds of a dense storage.
+    fn check_rect(&self, rect: IRect) -> Result<(), TileStorageError> {
+        let TileStorageData::Dense { bounds, .. } = &self.data else {
+            return Ok(());
+        };
+        if rect.min.cmplt(bounds.min).any() {
+            return Err(TileStorageError::OutOfBounds {
+                x: rect.min.x,
+                y: rect.min.y,
+            });
+        }
+        if rect.max.cmpgt(bounds.max).any() {
+            return Err(TileStorageError::OutOfBounds {
+                x: rect.max.x - 1,
+                y: rect.max.y - 1,
+            });
+        }
+        Ok(())
     }
 
+    /// Iterates the tiles of the chunk at `chunk_position` row by row, bottom to top. Positions
+    /// outside the bounds of a dense storage are `None`.
     pub fn iter_chunk_tiles(
         &self,
         chunk_position: IVec2,
     ) -> Result<impl Iterator<Item = Option<&TileData>>, TileStorageError> {
-        let chunk_size = self.chunk_size;
-        let chunk_rect = IRect::from_corners(
-            chunk_position * chunk_size,
-            (chunk_position + IVec2::splat(1)) * chunk_size,
-        );
+        let chunk_rect = self.chunk_rect(chunk_position);
 
-        self.iter_sub_rect(chunk_rect)
+        // Chunks on the edge of a dense storage may only be partially within its bounds
+        Ok(self.sub_rect_tiles(chunk_rect).into_iter())
     }
 }
 
@@ -239,8 +610,65 @@ pub enum TileStorageData {
     Sparse(HashMap<IVec2, TileData>),
     Dense {
         tiles: Vec<Option<TileData>>,
-        size: UVec2,
+        /// The tile positions held by `tiles`, stored row by row. `max` is exclusive.
+        bounds: IRect,
     },
+    /// One page for each chunk holding at least one tile, keyed by chunk position.
+    Chunked(HashMap<IVec2, TileChunkPage>),
+}
+
+/// The tiles of one chunk in a [`TileStorage::chunked`] storage.
+#[derive(Clone, Debug)]
+pub struct TileChunkPage {
+    tiles: Box<[Option<TileData>]>,
+    len: usize,
+}
+
+impl TileChunkPage {
+    fn new(chunk_size: IVec2) -> Self {
+        Self {
+            tiles: vec![None; chunk_size.element_product() as usize].into_boxed_slice(),
+            len: 0,
+        }
+    }
+
+    /// Returns the tiles of the chunk, stored row by row.
+    pub fn tiles(&self) -> &[Option<TileData>] {
+        &self.tiles
+    }
+
+    /// Returns the number of tiles in the chunk.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the chunk doesn't contain any tiles.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn set(&mut self, index: usize, tile_data: Option<TileData>) {
+        let old = core::mem::replace(&mut self.tiles[index], tile_data);
+        match (old.is_some(), self.tiles[index].is_some()) {
+            (false, true) => self.len += 1,
+            (true, false) => self.len -= 1,
+            _ => {}
+        }
+    }
+}
+
+/// Returns the index of the tile at `local_position` within its chunk in a [`TileChunkPage`].
+fn chunk_page_index(chunk_size: IVec2, local_position: IVec2) -> usize {
+    local_position.x as usize + local_position.y as usize * chunk_size.x as usize
+}
+
+/// Returns the index of `tile_position` in the tiles of a dense storage covering `bounds`.
+fn dense_index(bounds: &IRect, tile_position: IVec2) -> Option<usize> {
+    if tile_position.cmplt(bounds.min).any() || tile_position.cmpge(bounds.max).any() {
+        return None;
+    }
+    let offset = (tile_position - bounds.min).as_uvec2();
+    Some(offset.x as usize + offset.y as usize * bounds.width() as usize)
 }
 
 impl Default for TileStorageData {
@@ -248,3 +676,364 @@ impl Default for TileStorageData {
         Self::Sparse(HashMap::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirty_chunks(tile_storage: &TileStorage) -> Vec<IVec2> {
+        let mut chunk_positions: Vec<IVec2> =
+            tile_storage.iter_dirty_chunk_positions().copied().collect();
+        chunk_positions.sort_by_key(|pos| (pos.y, pos.x));
+        chunk_positions
+    }
+
+    fn storages(chunk_size: UVec2) -> [TileStorage; 3] {
+        let mut tile_storages = [
+            TileStorage::sparse(),
+            TileStorage::dense_rect(IRect::new(-50, -20, 50, 20)),
+            TileStorage::chunked(),
+        ];
+        for tile_storage in &mut tile_storages {
+            tile_storage.set_chunk_size(chunk_size).unwrap();
+        }
+        tile_storages
+    }
+
+    #[test]
+    fn set_all_dirty_includes_partial_chunks() {
+        let mut tile_storage = TileStorage::dense(UVec2::new(100, 70));
+        tile_storage.set_chunk_size(UVec2::new(32, 16)).unwrap();
+        tile_storage.clear_dirty_chunk_positions(dirty_chunks(&tile_storage).into_iter().collect());
+        tile_storage.set_all_dirty();
+
+        let expected: Vec<IVec2> = (0..5)
+            .flat_map(|y| (0..4).map(move |x| IVec2::new(x, y)))
+            .collect();
+        assert_eq!(dirty_chunks(&tile_storage), expected);
+    }
+
+    #[test]
+    fn set_all_dirty_with_negative_origin() {
+        let mut tile_storage = TileStorage::dense_rect(IRect::new(-50, -20, 50, 20));
+        tile_storage.set_all_dirty();
+
+        let expected: Vec<IVec2> = (-1..1)
+            .flat_map(|y| (-2..2).map(move |x| IVec2::new(x, y)))
+            .collect();
+        assert_eq!(dirty_chunks(&tile_storage), expected);
+    }
+
+    #[test]
+    fn set_marks_chunk_dirty() {
+        for mut tile_storage in storages(UVec2::new(16, 8)) {
+            tile_storage
+                .clear_dirty_chunk_positions(dirty_chunks(&tile_storage).into_iter().collect());
+            tile_storage
+                .set(IVec2::new(-17, 8), Some(TileData::from_index(1)))
+                .unwrap();
+            assert_eq!(dirty_chunks(&tile_storage), vec![IVec2::new(-2, 1)]);
+        }
+    }
+
+    #[test]
+    fn dirty_chunk_rect_covers_changed_tiles() {
+        for mut tile_storage in storages(UVec2::new(16, 8)) {
+            tile_storage
+                .clear_dirty_chunk_positions(dirty_chunks(&tile_storage).into_iter().collect());
+            tile_storage
+                .set(IVec2::new(3, 2), Some(TileData::from_index(1)))
+                .unwrap();
+            tile_storage.set(IVec2::new(5, 6), None).unwrap();
+            tile_storage
+                .set(IVec2::new(-2, 1), Some(TileData::from_index(1)))
+                .unwrap();
+
+            assert_eq!(
+                tile_storage.dirty_chunk_rect(IVec2::ZERO),
+                Some(IRect::new(3, 2, 6, 7))
+            );
+            assert_eq!(
+                tile_storage.dirty_chunk_rect(IVec2::new(-1, 0)),
+                Some(IRect::new(-2, 1, -1, 2))
+            );
+
+            tile_storage.set_all_dirty();
+            assert_eq!(
+                tile_storage.dirty_chunk_rect(IVec2::ZERO),
+                Some(tile_storage.chunk_rect(IVec2::ZERO))
+            );
+        }
+    }
+
+    #[test]
+    fn set_out_of_bounds() {
+        let mut tile_storage = TileStorage::dense(UVec2::new(10, 10));
+        tile_storage.clear_dirty_chunk_positions(dirty_chunks(&tile_storage).into_iter().collect());
+
+        assert_eq!(
+            tile_storage.set(IVec2::new(10, 0), Some(TileData::from_index(1))),
+            Err(TileStorageError::OutOfBounds { x: 10, y: 0 })
+        );
+        assert_eq!(
+            tile_storage.set(IVec2::new(-1, 5), Some(TileData::from_index(1))),
+            Err(TileStorageError::OutOfBounds { x: -1, y: 5 })
+        );
+        assert!(dirty_chunks(&tile_storage).is_empty());
+    }
+
+    #[test]
+    fn invalid_chunk_size() {
+        assert_eq!(
+            TileStorage::sparse().set_chunk_size(UVec2::new(0, 4)),
+            Err(TileStorageError::InvalidChunkSize { x: 0, y: 4 })
+        );
+    }
+
+    #[test]
+    fn chunk_emptiness_follows_tiles() {
+        for mut tile_storage in storages(UVec2::new(8, 4)) {
+            let chunk_position = IVec2::new(-1, 1);
+            assert!(tile_storage.is_chunk_empty(chunk_position));
+
+            for tile_position in [IVec2::new(-3, 5), IVec2::new(-8, 7)] {
+                tile_storage
+                    .set(tile_position, Some(TileData::from_index(1)))
+                    .unwrap();
+            }
+            // Overwriting a tile doesn't count it twice
+            tile_storage
+                .set(IVec2::new(-3, 5), Some(TileData::from_index(2)))
+                .unwrap();
+            assert!(!tile_storage.is_chunk_empty(chunk_position));
+            assert!(tile_storage.is_chunk_empty(IVec2::new(0, 1)));
+
+            tile_storage.set(IVec2::new(-3, 5), None).unwrap();
+            assert!(!tile_storage.is_chunk_empty(chunk_position));
+            tile_storage.set(IVec2::new(-8, 7), None).unwrap();
+            tile_storage.set(IVec2::new(-8, 7), None).unwrap();
+            assert!(tile_storage.is_chunk_empty(chunk_position));
+
+            tile_storage
+                .set(IVec2::new(-3, 5), Some(TileData::from_index(1)))
+                .unwrap();
+            tile_storage.set_chunk_size(UVec2::new(4, 4)).unwrap();
+            assert!(!tile_storage.is_chunk_empty(IVec2::new(-1, 1)));
+            assert!(tile_storage.is_chunk_empty(IVec2::new(-2, 1)));
+
+            tile_storage.fill(None).unwrap();
+            assert!(tile_storage.is_chunk_empty(IVec2::new(-1, 1)));
+        }
+    }
+
+    #[test]
+    fn invalid_tileset() {
+        let invalid_tile = TileData {
+            tileset: MAX_TILESETS as u8,
+            ..TileData::from_index(3)
+        };
+
+        for mut tile_storage in storages(UVec2::new(4, 4)) {
+            tile_storage
+                .set(IVec2::new(1, 1), Some(TileData::from_index(1)))
+                .unwrap();
+            assert_eq!(
+                tile_storage.set(IVec2::new(2, 2), Some(invalid_tile)),
+                Err(TileStorageError::InvalidTileset {
+                    tileset: MAX_TILESETS as u8
+                })
+            );
+            assert!(tile_storage.get(IVec2::new(2, 2)).unwrap().is_none());
+
+            // Invalid tiles are skipped, the rest of the rect is still filled
+            assert_eq!(
+                tile_storage.fill_rect_with(IRect::new(0, 0, 2, 1), |tile_position| {
+                    (tile_position.x == 0).then_some(invalid_tile)
+                }),
+                Err(TileStorageError::InvalidTileset {
+                    tileset: MAX_TILESETS as u8
+                })
+            );
+            assert!(tile_storage.get(IVec2::new(0, 0)).unwrap().is_none());
+
+            // Like rects, filling skips invalid tiles and reports the first of them
+            tile_storage
+                .set(IVec2::new(3, 3), Some(TileData::from_index(1)))
+                .unwrap();
+            let mut calls = 0;
+            assert_eq!(
+                tile_storage.fill_with(|| {
+                    calls += 1;
+                    Some(if calls == 2 {
+                        invalid_tile
+                    } else {
+                        TileData::from_index(4)
+                    })
+                }),
+                Err(TileStorageError::InvalidTileset {
+                    tileset: MAX_TILESETS as u8
+                })
+            );
+            let tileset_indices = [IVec2::new(1, 1), IVec2::new(3, 3)].map(|tile_position| {
+                tile_storage
+                    .get(tile_position)
+                    .unwrap()
+                    .unwrap()
+                    .tileset_index
+            });
+            assert!(tileset_indices.contains(&4));
+            tile_storage.fill(Some(TileData::from_index(1))).unwrap();
+
+            // Nothing is written when filling with a single invalid tile
+            assert_eq!(
+                tile_storage.fill(Some(invalid_tile)),
+                Err(TileStorageError::InvalidTileset {
+                    tileset: MAX_TILESETS as u8
+                })
+            );
+            assert_eq!(
+                tile_storage
+                    .get(IVec2::new(1, 1))
+                    .unwrap()
+                    .unwrap()
+                    .tileset_index,
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn iter_sub_rect_bounds() {
+        let tile_storage = TileStorage::dense(UVec2::new(10, 10));
+
+        assert_eq!(
+            tile_storage
+                .iter_sub_rect(IRect::new(0, 0, 10, 10))
+                .unwrap()
+                .count(),
+            100
+        );
+        assert!(
+            tile_storage
+                .iter_sub_rect(IRect::new(0, 0, 11, 10))
+                .is_err()
+        );
+        assert!(
+            tile_storage
+                .iter_sub_rect(IRect::new(-1, 0, 10, 10))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn iter_chunk_tiles_matches_across_backends() {
+        for chunk_size in [UVec2::new(32, 32), UVec2::new(16, 8), UVec2::new(7, 3)] {
+            let mut tile_storages = storages(chunk_size);
+            for tile_storage in &mut tile_storages {
+                tile_storage
+                    .fill_rect_with(IRect::new(-50, -20, 50, 20), |pos| {
+                        ((pos.x + pos.y) % 3 == 0)
+                            .then(|| TileData::from_index((pos.x + 50 + (pos.y + 20) * 100) as u16))
+                    })
+                    .unwrap();
+            }
+
+            let chunk_size = chunk_size.as_ivec2();
+            for chunk_y in -20i32.div_euclid(chunk_size.y)..=19i32.div_euclid(chunk_size.y) {
+                for chunk_x in -50i32.div_euclid(chunk_size.x)..=49i32.div_euclid(chunk_size.x) {
+                    let chunk_position = IVec2::new(chunk_x, chunk_y);
+                    let [sparse, dense, chunked] = tile_storages.each_ref().map(|tile_storage| {
+                        tile_storage
+                            .iter_chunk_tiles(chunk_position)
+                            .unwrap()
+                            .map(|tile| tile.map(|tile| tile.tileset_index))
+                            .collect::<Vec<_>>()
+                    });
+
+                    assert_eq!(sparse.len(), chunk_size.element_product() as usize);
+                    assert_eq!(sparse, dense);
+                    assert_eq!(sparse, chunked);
+
+                    let chunk_min = chunk_position * chunk_size;
+                    for (index, tile) in sparse.iter().enumerate() {
+                        let pos = chunk_min
+                            + IVec2::new(index as i32 % chunk_size.x, index as i32 / chunk_size.x);
+                        assert_eq!(
+                            *tile,
+                            tile_storages[0]
+                                .get(pos)
+                                .unwrap()
+                                .map(|tile| tile.tileset_index)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunked_storage_frees_empty_chunks() {
+        let mut tile_storage = TileStorage::chunked();
+        tile_storage
+            .set(IVec2::new(-1, -1), Some(TileData::from_index(1)))
+            .unwrap();
+        assert_eq!(
+            tile_storage
+                .chunk_page(IVec2::new(-1, -1))
+                .map(TileChunkPage::len),
+            Some(1)
+        );
+
+        tile_storage.set(IVec2::new(-1, -1), None).unwrap();
+        assert!(tile_storage.chunk_page(IVec2::new(-1, -1)).is_none());
+        assert!(tile_storage.is_chunk_empty(IVec2::new(-1, -1)));
+    }
+
+    #[test]
+    fn set_chunk_size_rechunks_and_dirties() {
+        for mut tile_storage in storages(UVec2::splat(32)) {
+            tile_storage
+                .set(IVec2::new(40, 5), Some(TileData::from_index(1)))
+                .unwrap();
+            tile_storage.set_chunk_size(UVec2::new(16, 4)).unwrap();
+
+            if tile_storage.bounds().is_some() {
+                assert_eq!(dirty_chunks(&tile_storage).len(), 8 * 10);
+            } else {
+                assert_eq!(dirty_chunks(&tile_storage), vec![IVec2::new(2, 1)]);
+            }
+            assert!(!tile_storage.is_chunk_empty(IVec2::new(2, 1)));
+            assert_eq!(
+                tile_storage
+                    .get(IVec2::new(40, 5))
+                    .unwrap()
+                    .unwrap()
+                    .tileset_index,
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn sparse_fill_only_touches_occupied_tiles() {
+        for mut tile_storage in [TileStorage::sparse(), TileStorage::chunked()] {
+            tile_storage
+                .set(IVec2::new(3, 3), Some(TileData::from_index(1)))
+                .unwrap();
+            tile_storage.fill(Some(TileData::from_index(2))).unwrap();
+
+            assert_eq!(
+                tile_storage
+                    .get(IVec2::new(3, 3))
+                    .unwrap()
+                    .unwrap()
+                    .tileset_index,
+                2
+            );
+            assert!(tile_storage.get(IVec2::new(4, 3)).unwrap().is_none());
+
+            tile_storage.fill(None).unwrap();
+            assert!(tile_storage.is_chunk_empty(IVec2::ZERO));
+        }
+    }
+}
@@ -1,26 +1,34 @@
 use crate::MeshMaterial2d;
 use bevy::{
-    asset::RenderAssetUsages,
+    asset::{AssetEvents, RenderAssetUsages},
     ecs::{component::HookContext, world::DeferredWorld},
     image::ImageSampler,
-    math::FloatOrd,
     platform::collections::{HashMap, HashSet},
     prelude::*,
     render::{
-        mesh::{Indices, PrimitiveTopology},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+        mesh::{Indices, MeshTag, PrimitiveTopology},
+        primitives::Aabb,
+        render_asset::RenderAssets,
         render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+            CommandEncoderDescriptor, Extent3d, Origin3d, TexelCopyBufferLayout,
+            TexelCopyTextureInfo, TextureAspect, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsages,
         },
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+        view::{RenderLayers, VisibilitySystems},
     },
 };
 use bytemuck::{Pod, Zeroable};
 #[cfg(target_arch = "wasm32")]
 use tracing::error;
-use tracing::warn;
 
 use super::{
-    ATTRIBUTE_TILE_INDEX, TileData, TileStorage, TilemapChunkMaterial, TilemapInfo, TilemapLayer,
-    TilemapRenderMode, Tileset,
+    ATTRIBUTE_TILE_INDEX, AdditionalTilesets, MAX_TILESETS, TileData, TileStorage,
+    TilemapChunkMaterial, TilemapInfo, TilemapLayer, TilemapRenderMode, TilemapSharedTileData,
+    Tileset, TilesetAnimationData, TilesetsChanged, iter_layer_tilesets,
+    update_tileset_animation_data,
 };
 
 /// Plugin that handles the initialization and updating of tilemap chunks.
@@ -29,14 +37,160 @@ pub struct TilemapChunkPlugin;
 
 impl Plugin for TilemapChunkPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<TilemapChunkMeshCache>().add_systems(
+        app.init_resource::<TilemapChunkMeshCache>()
+            .init_resource::<TilemapChunkUploadBudget>()
+            .init_resource::<TilemapChunkTileDataWrites>()
+            .add_systems(
+                PreUpdate,
+                (
+                    update_tileset_animation_data,
+                    despawn_outdated_tilemap_chunks,
+                    update_tilemap_chunk_tilesets,
+                    spawn_missing_tilemap_chunks,
+                )
+                    .chain(),
+            )
+            // Tile data is written once the visibility of this frame is known, so chunks that
+            // come into view are updated before they're drawn, and before asset events are sent
+            // so new materials are extracted the same frame
+            .add_systems(
+                PostUpdate,
+                (prepare_shared_tile_data, update_dirty_tilemap_chunks)
+                    .chain()
+                    .after(VisibilitySystems::CheckVisibility)
+                    .before(AssetEvents),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    update_tilemap_chunk_aabbs,
+                    update_tilemap_chunk_render_layers,
+                )
+                    .after(spawn_missing_tilemap_chunks),
+            );
+
+        #[cfg(feature = "gizmos")]
+        app.add_systems(
             PreUpdate,
-            (spawn_missing_tilemap_chunks, update_visible_tilemap_chunks).chain(),
+            update_tilemap_chunk_aabb_gizmos.after(spawn_missing_tilemap_chunks),
         );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<TilemapChunkTileDataWrites>()
+            .add_systems(ExtractSchedule, extract_tilemap_chunk_tile_data_writes)
+            .add_systems(
+                Render,
+                write_tilemap_chunk_tile_data.in_set(RenderSet::PrepareResources),
+            );
     }
 }
 
-type TilemapChunkMeshCacheKey = (UVec2, FloatOrd, FloatOrd, TilemapRenderMode);
+/// Tiles written to part of a chunk's tile data texture.
+#[derive(Clone)]
+struct TileDataWrite {
+    image: AssetId<Image>,
+    /// The first texel written, i.e. the position of the first tile within the chunk.
+    origin: UVec2,
+    size: UVec2,
+    tiles: Vec<PackedTileData>,
+}
+
+/// The tiles of a tile data texture copied to the texture replacing it, when a
+/// [`TilemapSharedTileData`] texture grows.
+#[derive(Clone)]
+struct TileDataCopy {
+    /// Kept alive until the copy is made.
+    source: Handle<Image>,
+    destination: AssetId<Image>,
+    size: UVec2,
+}
+
+/// Writes to the tile data textures of existing chunks.
+///
+/// Modifying an [`Image`] asset re-uploads the whole texture, so changed tiles are written
+/// directly to the GPU texture instead. The tile data images only live in the render world.
+#[derive(Resource, Default)]
+struct TilemapChunkTileDataWrites {
+    copies: Vec<TileDataCopy>,
+    writes: Vec<TileDataWrite>,
+}
+
+fn extract_tilemap_chunk_tile_data_writes(
+    mut render_writes: ResMut<TilemapChunkTileDataWrites>,
+    writes: Extract<Res<TilemapChunkTileDataWrites>>,
+) {
+    render_writes.copies.extend(writes.copies.iter().cloned());
+    render_writes.writes.extend(writes.writes.iter().cloned());
+}
+
+fn write_tilemap_chunk_tile_data(
+    mut writes: ResMut<TilemapChunkTileDataWrites>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    // Copies are submitted before any of this frame's writes are queued, which only run at the
+    // next submission, so the old tiles don't overwrite the ones written since
+    if !writes.copies.is_empty() {
+        let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("tilemap_chunk_tile_data_copies"),
+        });
+        for copy in writes.copies.drain(..) {
+            let (Some(source), Some(destination)) = (
+                gpu_images.get(&copy.source),
+                gpu_images.get(copy.destination),
+            ) else {
+                continue;
+            };
+            command_encoder.copy_texture_to_texture(
+                source.texture.as_image_copy(),
+                destination.texture.as_image_copy(),
+                Extent3d {
+                    width: copy.size.x,
+                    height: copy.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        render_queue.submit([command_encoder.finish()]);
+    }
+
+    // Tile data images are prepared the frame they're created, before any writes to them. Writes
+    // to images that no longer exist belong to despawned chunks and are dropped.
+    for write in writes.writes.drain(..) {
+        let Some(gpu_image) = gpu_images.get(write.image) else {
+            continue;
+        };
+        render_queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &gpu_image.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: write.origin.x,
+                    y: write.origin.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            bytemuck::cast_slice(&write.tiles),
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(write.size.x * size_of::<PackedTileData>() as u32),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width: write.size.x,
+                height: write.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+type TilemapChunkMeshCacheKey = (UVec2, UVec2, UVec2, TilemapRenderMode, IVec2);
 
 /// A resource storing the meshes for each tilemap chunk size.
 #[derive(Resource, Default, Deref, DerefMut)]
@@ -55,13 +209,18 @@ pub struct TilemapChunkMeshCache(HashMap<TilemapChunkMeshCacheKey, Handle<Mesh>>
 pub struct TilemapChunk {
     tilemap_layer: Entity,
     location: IVec2,
+    // The layout the chunk's mesh, transform and bounds were built for
+    chunk_size: UVec2,
+    tile_size: UVec2,
+    grid_size: UVec2,
+    render_mode: TilemapRenderMode,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 struct PackedTileData {
     tileset_index: u16,
-    flags: u16,    // flags (visibility, etc.)
+    flags: u16, // bit 0: visible, bits 1-3: flip x, y, d, bit 4: animated, bits 5-6: tileset
     color_rg: u16, // r in low 8 bits, g in high 8 bits
     color_ba: u16, // b in low 8 bits, a in high 8 bits
 }
@@ -72,7 +231,12 @@ impl PackedTileData {
 
         Self {
             tileset_index: tile.tileset_index,
-            flags: tile.visible as u16,
+            flags: tile.visible as u16
+                | (tile.flip.x as u16) << 1
+                | (tile.flip.y as u16) << 2
+                | (tile.flip.d as u16) << 3
+                | (tile.animated as u16) << 4
+                | (tile.tileset as u16) << 5,
             color_rg: (r as u16) | ((g as u16) << 8),
             color_ba: (b as u16) | ((a as u16) << 8),
         }
@@ -113,52 +277,172 @@ fn on_tilemap_chunk_remove(mut world: DeferredWorld, HookContext { entity, .. }:
         return;
     };
 
-    tilemap_layer.chunks.remove(&location);
+    // The layer may already have replaced this chunk, e.g. after a chunk size change
+    if tilemap_layer.chunks.get(&location) == Some(&entity) {
+        tilemap_layer.chunks.remove(&location);
+    }
 }
 
-fn spawn_missing_tilemap_chunks(
+/// Despawns a tilemap chunk along with the material and tile data image it owns, unless they're
+/// its layer's [`TilemapSharedTileData`].
+fn despawn_tilemap_chunk(
+    commands: &mut Commands,
+    chunk_entity: Entity,
+    chunk_material: &MeshMaterial2d<TilemapChunkMaterial>,
+    shared_tile_data: Option<&TilemapSharedTileData>,
+    chunk_materials: &mut Assets<TilemapChunkMaterial>,
+    images: &mut Assets<Image>,
+) {
+    let is_shared = shared_tile_data
+        .is_some_and(|shared_tile_data| shared_tile_data.material.id() == chunk_material.id());
+    if chunk_material.is_strong() && !is_shared {
+        if let Some(material) = chunk_materials.remove(chunk_material.id()) {
+            images.remove(&material.tile_data);
+        }
+    }
+    commands.entity(chunk_entity).despawn();
+}
+
+/// A tilemap layer along with the tilesets it draws from.
+type TilemapLayerTilesets = (
+    &'static TilemapLayer,
+    &'static Tileset,
+    Option<&'static AdditionalTilesets>,
+);
+
+/// Updates the materials of existing chunks when the tilesets of their layer change, so tiles
+/// are drawn from the current tilesets rather than the ones the chunk was created with.
+fn update_tilemap_chunk_tilesets(
     tilemap_layer_query: Query<
-        (Entity, &TilemapLayer, &TileStorage, &Tileset),
-        Changed<TileStorage>,
+        (TilemapLayerTilesets, Option<&TilemapSharedTileData>),
+        TilesetsChanged,
     >,
+    chunk_query: Query<&MeshMaterial2d<TilemapChunkMaterial>>,
+    mut chunk_materials: ResMut<Assets<TilemapChunkMaterial>>,
+) {
+    for ((tilemap_layer, tileset, additional_tilesets), shared_tile_data) in &tilemap_layer_query {
+        let material_ids: HashSet<_> = tilemap_layer
+            .chunks
+            .values()
+            .filter_map(|chunk_entity| chunk_query.get(*chunk_entity).ok())
+            .map(|chunk_material| chunk_material.id())
+            .chain(shared_tile_data.map(|shared_tile_data| shared_tile_data.material.id()))
+            .collect();
+        for material_id in material_ids {
+            if let Some(material) = chunk_materials.get_mut(material_id) {
+                set_chunk_material_tilesets(material, tilemap_layer, tileset, additional_tilesets);
+            }
+        }
+    }
+}
+
+type RebuiltTilemapLayer = (
+    &'static mut TilemapLayer,
+    &'static mut TileStorage,
+    &'static Tileset,
+    Option<&'static AdditionalTilesets>,
+    Option<&'static TilemapSharedTileData>,
+);
+
+type TilemapLayoutChanged = Or<(
+    Changed<TileStorage>,
+    Changed<Tileset>,
+    Changed<TilemapLayer>,
+)>;
+
+/// Despawns all chunks of layers whose chunk size, tile size, grid size or render mode has
+/// changed, so they're respawned with a new mesh, transform and material for the new layout.
+fn despawn_outdated_tilemap_chunks(
+    mut tilemap_layer_query: Query<RebuiltTilemapLayer, TilemapLayoutChanged>,
+    chunk_query: Query<(&TilemapChunk, &MeshMaterial2d<TilemapChunkMaterial>)>,
+    mut chunk_materials: ResMut<Assets<TilemapChunkMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut commands: Commands,
+) {
+    for (mut tilemap_layer, mut tile_storage, tileset, additional_tilesets, shared_tile_data) in
+        &mut tilemap_layer_query
+    {
+        let chunk_size = tile_storage.chunk_size();
+        let grid_size = tilemap_layer.grid_size_for(tileset);
+        // Outdated chunks are all despawned at once, so the chunks of a layer share one layout
+        let outdated = tilemap_layer
+            .chunks
+            .values()
+            .next()
+            .is_some_and(|chunk_entity| {
+                chunk_query.get(*chunk_entity).is_ok_and(|(chunk, _)| {
+                    chunk.chunk_size != chunk_size
+                        || chunk.tile_size != tileset.tile_size
+                        || chunk.grid_size != grid_size
+                        || chunk.render_mode != tilemap_layer.render_mode
+                })
+            });
+        if !outdated {
+            continue;
+        }
+
+        for (_, chunk_entity) in tilemap_layer.chunks.drain() {
+            if let Ok((_, chunk_material)) = chunk_query.get(chunk_entity) {
+                despawn_tilemap_chunk(
+                    &mut commands,
+                    chunk_entity,
+                    chunk_material,
+                    shared_tile_data,
+                    &mut chunk_materials,
+                    &mut images,
+                );
+            }
+        }
+
+        // The respawned chunks need all of their tiles. Unlike the chunk materials, the shared
+        // material outlives the chunks, and its tile quads depend on the grid size.
+        tile_storage.set_all_dirty();
+        if let Some(material) = shared_tile_data
+            .and_then(|shared_tile_data| chunk_materials.get_mut(shared_tile_data.material.id()))
+        {
+            set_chunk_material_tilesets(material, &tilemap_layer, tileset, additional_tilesets);
+        }
+    }
+}
+
+fn spawn_missing_tilemap_chunks(
+    tilemap_layer_query: Query<(Entity, &TileStorage, TilemapLayerTilesets), Changed<TileStorage>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut tilemap_chunk_mesh_cache: ResMut<TilemapChunkMeshCache>,
 ) {
-    for (tilemap_layer_entity, tilemap, tile_storage, tileset) in tilemap_layer_query {
+    for (tilemap_layer_entity, tile_storage, (tilemap, tileset, additional_tilesets)) in
+        tilemap_layer_query
+    {
         let chunk_size = tile_storage.chunk_size();
-        let display_size = (chunk_size * tileset.tile_size).as_vec2();
+        let grid_size = tilemap.grid_size_for(tileset);
+        let quad_bounds = layer_quad_bounds(tilemap, tileset, additional_tilesets);
 
         for chunk_position in tile_storage
             .iter_dirty_chunk_positions()
-            .filter(|pos| !tilemap.chunks.contains_key(*pos))
+            .filter(|pos| !tilemap.chunks.contains_key(*pos) && !tile_storage.is_chunk_empty(**pos))
         {
-            let chunk_world_position = match tilemap.render_mode {
-                TilemapRenderMode::Orthogonal => chunk_position.as_vec2() * display_size,
-                TilemapRenderMode::Isometric => {
-                    let chunk_x = chunk_position.x as f32;
-                    let chunk_y = chunk_position.y as f32;
-
-                    // TODO: Don't hardcode these offsets
-                    let iso_x = (chunk_x - chunk_y) * display_size.x * 0.5;
-                    let iso_y = (chunk_x + chunk_y) * display_size.y * 0.25;
-
-                    Vec2::new(iso_x, iso_y)
-                }
-            };
+            let chunk_origin = chunk_position * chunk_size.as_ivec2();
+            let chunk_world_position = tilemap
+                .render_mode
+                .tile_to_local(chunk_origin, grid_size.as_vec2());
+            let mesh_origin = tilemap.render_mode.chunk_mesh_origin(chunk_origin);
 
             let mesh_key: TilemapChunkMeshCacheKey = (
                 chunk_size,
-                FloatOrd(display_size.x),
-                FloatOrd(display_size.y),
+                tileset.tile_size,
+                grid_size,
                 tilemap.render_mode,
+                mesh_origin,
             );
 
             let mesh = tilemap_chunk_mesh_cache.entry(mesh_key).or_insert_with(|| {
                 meshes.add(make_chunk_mesh(
                     &chunk_size,
-                    &display_size,
+                    &tileset.tile_size.as_vec2(),
+                    &grid_size.as_vec2(),
                     tilemap.render_mode,
+                    mesh_origin,
                 ))
             });
 
@@ -167,33 +451,242 @@ fn spawn_missing_tilemap_chunks(
                 TilemapChunk {
                     tilemap_layer: tilemap_layer_entity,
                     location: *chunk_position,
+                    chunk_size,
+                    tile_size: tileset.tile_size,
+                    grid_size,
+                    render_mode: tilemap.render_mode,
                 },
                 Transform::from_translation(chunk_world_position.extend(0.0)),
                 Mesh2d(mesh.clone()),
+                make_chunk_aabb(
+                    &chunk_size,
+                    &grid_size.as_vec2(),
+                    tilemap.render_mode,
+                    mesh_origin,
+                    quad_bounds,
+                ),
                 ChildOf(tilemap_layer_entity),
             ));
         }
     }
 }
 
-fn update_visible_tilemap_chunks(
-    tilemap_layer_query: Query<(Entity, &TilemapLayer, &mut TileStorage, &Tileset)>,
-    mut chunk_query: Query<(
-        Entity,
-        &TilemapChunk,
-        &mut MeshMaterial2d<TilemapChunkMaterial>,
-        &ViewVisibility,
-    )>,
+/// Copies the [`RenderLayers`] of tilemap layers to their chunks, so chunks are only drawn by
+/// the cameras that can see their layer.
+fn update_tilemap_chunk_render_layers(
+    new_chunk_query: Query<(Entity, &TilemapChunk), Added<TilemapChunk>>,
+    changed_tilemap_layer_query: Query<(&TilemapLayer, &RenderLayers), Changed<RenderLayers>>,
+    tilemap_layer_query: Query<(&TilemapLayer, Option<&RenderLayers>)>,
+    mut removed_render_layers: RemovedComponents<RenderLayers>,
+    mut commands: Commands,
+) {
+    for (chunk_entity, chunk) in &new_chunk_query {
+        if let Ok((_, Some(render_layers))) = tilemap_layer_query.get(chunk.tilemap_layer) {
+            commands
+                .entity(chunk_entity)
+                .try_insert(render_layers.clone());
+        }
+    }
+
+    for (tilemap_layer, render_layers) in &changed_tilemap_layer_query {
+        for chunk_entity in tilemap_layer.chunks.values() {
+            commands
+                .entity(*chunk_entity)
+                .try_insert(render_layers.clone());
+        }
+    }
+
+    for tilemap_layer_entity in removed_render_layers.read() {
+        let Ok((tilemap_layer, None)) = tilemap_layer_query.get(tilemap_layer_entity) else {
+            continue;
+        };
+        for chunk_entity in tilemap_layer.chunks.values() {
+            commands.entity(*chunk_entity).try_remove::<RenderLayers>();
+        }
+    }
+}
+
+/// Recomputes the bounds of existing chunks when the tilesets of their layer change, since tiles
+/// with a different offset or from a different additional tileset can overhang their grid cells
+/// by a different amount. Chunks whose tile size changed are respawned by
+/// [`despawn_outdated_tilemap_chunks`] instead.
+fn update_tilemap_chunk_aabbs(
+    tilemap_layer_query: Query<(&TileStorage, TilemapLayerTilesets), TilesetsChanged>,
+    chunk_query: Query<&TilemapChunk>,
+    mut commands: Commands,
+) {
+    for (tile_storage, (tilemap, tileset, additional_tilesets)) in tilemap_layer_query {
+        let chunk_size = tile_storage.chunk_size();
+        let grid_size = tilemap.grid_size_for(tileset).as_vec2();
+        let quad_bounds = layer_quad_bounds(tilemap, tileset, additional_tilesets);
+
+        for chunk_entity in tilemap.chunks.values() {
+            let Ok(chunk) = chunk_query.get(*chunk_entity) else {
+                continue;
+            };
+            let chunk_origin = chunk.location * chunk_size.as_ivec2();
+            commands.entity(*chunk_entity).try_insert(make_chunk_aabb(
+                &chunk.chunk_size,
+                &grid_size,
+                tilemap.render_mode,
+                tilemap.render_mode.chunk_mesh_origin(chunk_origin),
+                quad_bounds,
+            ));
+        }
+    }
+}
+
+/// Draws the [`Aabb`] of every chunk of a tilemap layer, which is what Bevy's visibility system
+/// uses to cull chunks outside the view.
+///
+/// Insert this on a [`TilemapLayer`] to show the bounds of its chunks, and remove it to hide
+/// them again. Requires the `gizmos` feature.
+#[cfg(feature = "gizmos")]
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+pub struct ShowTilemapChunkAabbs {
+    /// Color of the gizmos, or the default of
+    /// [`AabbGizmoConfigGroup`](bevy::gizmos::aabb::AabbGizmoConfigGroup) if `None`.
+    pub color: Option<Color>,
+}
+
+#[cfg(feature = "gizmos")]
+fn update_tilemap_chunk_aabb_gizmos(
+    new_chunk_query: Query<(Entity, &TilemapChunk), Added<TilemapChunk>>,
+    changed_tilemap_layer_query: Query<
+        (&TilemapLayer, &ShowTilemapChunkAabbs),
+        Changed<ShowTilemapChunkAabbs>,
+    >,
+    tilemap_layer_query: Query<(&TilemapLayer, Option<&ShowTilemapChunkAabbs>)>,
+    mut removed_gizmos: RemovedComponents<ShowTilemapChunkAabbs>,
+    mut commands: Commands,
+) {
+    use bevy::gizmos::aabb::ShowAabbGizmo;
+
+    for (chunk_entity, chunk) in &new_chunk_query {
+        if let Ok((_, Some(show_aabbs))) = tilemap_layer_query.get(chunk.tilemap_layer) {
+            commands.entity(chunk_entity).try_insert(ShowAabbGizmo {
+                color: show_aabbs.color,
+            });
+        }
+    }
+
+    for (tilemap_layer, show_aabbs) in &changed_tilemap_layer_query {
+        for chunk_entity in tilemap_layer.chunks.values() {
+            commands.entity(*chunk_entity).try_insert(ShowAabbGizmo {
+                color: show_aabbs.color,
+            });
+        }
+    }
+
+    for tilemap_layer_entity in removed_gizmos.read() {
+        let Ok((tilemap_layer, None)) = tilemap_layer_query.get(tilemap_layer_entity) else {
+            continue;
+        };
+        for chunk_entity in tilemap_layer.chunks.values() {
+            commands.entity(*chunk_entity).try_remove::<ShowAabbGizmo>();
+        }
+    }
+}
+
+/// Limits how much tile data is written to tilemap chunks each frame.
+///
+/// Visible dirty chunks closest to a camera are updated first, so large edits such as spawning a
+/// whole map stream in over several frames instead of stalling a single one. At least one chunk is
+/// updated every frame, whatever the budget. New chunks get their material outside of the budget
+/// and are drawn empty until their tiles are written.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct TilemapChunkUploadBudget {
+    /// Maximum number of chunks to update per frame, or `None` for no limit.
+    pub max_chunks: Option<usize>,
+    /// Maximum number of bytes of tile data to upload per frame, or `None` for no limit.
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for TilemapChunkUploadBudget {
+    fn default() -> Self {
+        Self {
+            max_chunks: Some(64),
+            max_bytes: None,
+        }
+    }
+}
+
+impl TilemapChunkUploadBudget {
+    /// A budget that updates every dirty chunk in the frame it becomes dirty.
+    pub const UNLIMITED: Self = Self {
+        max_chunks: None,
+        max_bytes: None,
+    };
+}
+
+/// A visible dirty chunk waiting for its tile data to be written.
+struct PendingChunkUpdate {
+    tilemap_layer: Entity,
+    chunk: Entity,
+    /// Squared distance from the chunk's center to the nearest camera.
+    distance: f32,
+}
+
+type DirtyTilemapLayer = (
+    Entity,
+    &'static TilemapLayer,
+    &'static mut TileStorage,
+    &'static Tileset,
+    Option<&'static AdditionalTilesets>,
+    Option<&'static TilesetAnimationData>,
+    Option<&'static mut TilemapSharedTileData>,
+);
+
+type DirtyTilemapChunk = (
+    &'static TilemapChunk,
+    &'static mut MeshMaterial2d<TilemapChunkMaterial>,
+    &'static ViewVisibility,
+    &'static GlobalTransform,
+    Option<&'static Aabb>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn update_dirty_tilemap_chunks(
+    mut tilemap_layer_query: Query<DirtyTilemapLayer>,
+    mut chunk_query: Query<DirtyTilemapChunk>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    upload_budget: Res<TilemapChunkUploadBudget>,
+    mut tile_data_writes: ResMut<TilemapChunkTileDataWrites>,
     mut chunk_materials: ResMut<Assets<TilemapChunkMaterial>>,
     mut images: ResMut<Assets<Image>>,
-    #[cfg(target_arch = "wasm32")] mut commands: Commands,
+    mut commands: Commands,
 ) {
-    for (tilemap_layer_entity, tilemap_layer, mut tile_storage, tileset) in tilemap_layer_query {
+    // Last frame's writes have been extracted by now
+    tile_data_writes.writes.clear();
+
+    let camera_positions: Vec<Vec2> = camera_query
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation().truncate())
+        .collect();
+
+    let mut chunk_positions_to_clear: HashMap<Entity, HashSet<IVec2>> = HashMap::new();
+    let mut pending_updates = Vec::new();
+
+    for (
+        tilemap_layer_entity,
+        tilemap_layer,
+        tile_storage,
+        tileset,
+        additional_tilesets,
+        animation_data,
+        mut shared_tile_data,
+    ) in &mut tilemap_layer_query
+    {
         #[cfg(target_arch = "wasm32")]
-        if let Some(tileset_image) = images.get(&tileset.image) {
+        for tileset_image in iter_layer_tilesets(tileset, additional_tilesets)
+            .filter_map(|tileset| images.get(&tileset.image))
+        {
             let layer_count = tileset_image.texture_descriptor.array_layer_count();
             if layer_count % 6 == 0 {
-                commands.entity(tilemap_layer_entity).remove::<Tileset>();
+                commands
+                    .entity(tilemap_layer_entity)
+                    .remove::<(Tileset, AdditionalTilesets)>();
 
                 if layer_count == 6 {
                     error!(
@@ -206,80 +699,336 @@ fn update_visible_tilemap_chunks(
                     );
                 }
             }
+        }
+
+        let Some(animation_data) = animation_data else {
+            continue;
         };
 
-        let mut chunk_positions_to_clear = HashSet::new();
+        let chunk_size = tile_storage.chunk_size();
+        let chunk_positions_to_clear = chunk_positions_to_clear
+            .entry(tilemap_layer_entity)
+            .or_default();
         for chunk_pos in tile_storage.iter_dirty_chunk_positions() {
             let Some(chunk_entity) = tilemap_layer.chunks.get(chunk_pos) else {
+                // Chunks are only spawned once they contain tiles
+                if tile_storage.is_chunk_empty(*chunk_pos) {
+                    chunk_positions_to_clear.insert(*chunk_pos);
+                }
                 continue;
             };
-            let Ok((chunk_entity, chunk, mut chunk_material, visibility)) =
+            let Ok((chunk, mut chunk_material, visibility, chunk_transform, aabb)) =
                 chunk_query.get_mut(*chunk_entity)
             else {
                 continue;
             };
-            if !visibility.get() {
+
+            if tile_storage.is_chunk_empty(chunk.location) {
+                chunk_positions_to_clear.insert(chunk.location);
+                despawn_tilemap_chunk(
+                    &mut commands,
+                    *chunk_entity,
+                    &chunk_material,
+                    shared_tile_data.as_deref(),
+                    &mut chunk_materials,
+                    &mut images,
+                );
                 continue;
             }
 
-            chunk_positions_to_clear.insert(chunk.location);
+            // New chunks get their material right away, outside of the upload budget, so they
+            // never render or cull without one. Their tile data starts out empty and is written
+            // like that of any other dirty chunk.
+            match &mut shared_tile_data {
+                Some(shared_tile_data) => {
+                    if chunk_material.id() != shared_tile_data.material.id() {
+                        let Some((slot, reused)) = shared_tile_data.allocate_slot(chunk.location)
+                        else {
+                            continue;
+                        };
+                        if reused {
+                            // Clear the tiles of the chunk the slot belonged to before
+                            tile_data_writes.writes.push(TileDataWrite {
+                                image: shared_tile_data.image.id(),
+                                origin: shared_tile_data.slot_origin(slot),
+                                size: chunk_size,
+                                tiles: vec![
+                                    PackedTileData::empty();
+                                    chunk_size.element_product() as usize
+                                ],
+                            });
+                        }
+                        *chunk_material = MeshMaterial2d(shared_tile_data.material.clone());
+                        commands.entity(*chunk_entity).insert(MeshTag(slot));
+                    }
+                }
+                None => {
+                    if !chunk_materials.contains(chunk_material.id()) {
+                        let tile_data = images.add(make_chunk_tile_data_image(&chunk_size));
+                        let material = chunk_materials.add(make_chunk_material(
+                            tilemap_layer,
+                            tileset,
+                            additional_tilesets,
+                            animation_data,
+                            tile_data,
+                            chunk_size,
+                            chunk.location,
+                        ));
+                        *chunk_material = MeshMaterial2d(material);
+                    }
+                }
+            }
 
-            let chunk_size = tile_storage.chunk_size();
-            let Ok(chunk_tiles) = tile_storage.iter_chunk_tiles(chunk.location) else {
-                warn!(
-                    "Unable to access TileStorage data for tilemap chunk {} in tilemap layer {}",
-                    chunk_entity, tilemap_layer_entity
-                );
+            // Off-screen chunks are updated once they become visible, which their `Aabb`
+            // covering all of their tiles makes reliable
+            if !visibility.get() {
                 continue;
-            };
+            }
 
-            let packed_tiles: Vec<PackedTileData> = chunk_tiles
+            let chunk_center = chunk_transform
+                .transform_point(aabb.map_or(Vec3::ZERO, |aabb| aabb.center.into()))
+                .truncate();
+            let distance = camera_positions
+                .iter()
+                .map(|camera_position| camera_position.distance_squared(chunk_center))
+                .reduce(f32::min)
+                .unwrap_or_default();
+
+            pending_updates.push(PendingChunkUpdate {
+                tilemap_layer: tilemap_layer_entity,
+                chunk: *chunk_entity,
+                distance,
+            });
+        }
+    }
+
+    pending_updates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    let mut updated_chunks = 0;
+    let mut uploaded_bytes = 0;
+    for pending_update in pending_updates {
+        let Ok((tilemap_layer_entity, _, tile_storage, .., shared_tile_data)) =
+            tilemap_layer_query.get(pending_update.tilemap_layer)
+        else {
+            continue;
+        };
+        let Ok((chunk, chunk_material, ..)) = chunk_query.get(pending_update.chunk) else {
+            continue;
+        };
+
+        // A chunk's tiles outside of its dirty rect are either already written or empty, which
+        // is what new tile data textures start out as
+        let chunk_rect = tile_storage.chunk_rect(chunk.location);
+        let upload_rect = tile_storage
+            .dirty_chunk_rect(chunk.location)
+            .unwrap_or(chunk_rect)
+            .intersect(chunk_rect);
+        let upload_bytes =
+            upload_rect.size().element_product() as usize * size_of::<PackedTileData>();
+        if updated_chunks > 0
+            && (upload_budget
+                .max_chunks
+                .is_some_and(|max_chunks| updated_chunks >= max_chunks)
+                || upload_budget
+                    .max_bytes
+                    .is_some_and(|max_bytes| uploaded_bytes + upload_bytes > max_bytes))
+        {
+            break;
+        }
+
+        let upload_offset = (upload_rect.min - chunk_rect.min).as_uvec2();
+        let (image, origin) = match shared_tile_data {
+            Some(shared_tile_data) => {
+                let Some(slot) = shared_tile_data.slot(chunk.location) else {
+                    continue;
+                };
+                (
+                    shared_tile_data.image.id(),
+                    shared_tile_data.slot_origin(slot) + upload_offset,
+                )
+            }
+            None => {
+                let Some(material) = chunk_materials.get(chunk_material.id()) else {
+                    continue;
+                };
+                (material.tile_data.id(), upload_offset)
+            }
+        };
+
+        updated_chunks += 1;
+        uploaded_bytes += upload_bytes;
+
+        chunk_positions_to_clear
+            .entry(tilemap_layer_entity)
+            .or_default()
+            .insert(chunk.location);
+
+        tile_data_writes.writes.push(TileDataWrite {
+            image,
+            origin,
+            size: upload_rect.size().as_uvec2(),
+            tiles: tile_storage
+                .sub_rect_tiles(upload_rect)
+                .into_iter()
                 .map(|tile_opt| {
                     tile_opt
                         .map(PackedTileData::new)
                         .unwrap_or_else(PackedTileData::empty)
                 })
-                .collect();
+                .collect(),
+        });
+    }
 
-            if let Some(material) = chunk_materials.get_mut(chunk_material.id()) {
-                let Some(chunk_image) = images.get_mut(&material.tile_data) else {
-                    return;
-                };
-                let Some(data) = chunk_image.data.as_mut() else {
-                    warn!(
-                        "TilemapChunkMaterial tile data image data not found for tilemap chunk {} in tilemap layer {}",
-                        chunk_entity, tilemap_layer_entity
-                    );
-                    return;
-                };
-                data.clear();
-                data.extend_from_slice(bytemuck::cast_slice(&packed_tiles));
-            } else {
-                let tile_data_image = make_chunk_tile_data_image(&chunk_size, &packed_tiles);
-
-                let material = chunk_materials.add(TilemapChunkMaterial {
-                    alpha_mode: tilemap_layer.alpha_mode,
-                    tileset: tileset.image.clone(),
-                    tile_data: images.add(tile_data_image),
-                    tilemap_info: TilemapInfo {
-                        tile_size: tileset.tile_size.as_vec2(),
-                        chunk_size,
-                        chunk_position: chunk.location,
-                        layer_z_index: tilemap_layer.z_index,
-                    },
-                });
+    for (tilemap_layer_entity, chunk_positions) in chunk_positions_to_clear {
+        if let Ok((_, _, mut tile_storage, ..)) = tilemap_layer_query.get_mut(tilemap_layer_entity)
+        {
+            tile_storage.clear_dirty_chunk_positions(chunk_positions);
+        }
+    }
+}
+
+type SharedTileDataLayer = (
+    &'static TilemapLayer,
+    &'static TileStorage,
+    &'static Tileset,
+    Option<&'static AdditionalTilesets>,
+    &'static TilesetAnimationData,
+    &'static mut TilemapSharedTileData,
+);
+
+/// Keeps the shared material and tile data texture of layers with [`TilemapSharedTileData`] large
+/// enough for all of their chunks.
+fn prepare_shared_tile_data(
+    mut tilemap_layer_query: Query<SharedTileDataLayer>,
+    mut tile_data_writes: ResMut<TilemapChunkTileDataWrites>,
+    mut chunk_materials: ResMut<Assets<TilemapChunkMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // Last frame's copies have been extracted by now
+    tile_data_writes.copies.clear();
+
+    for (
+        tilemap_layer,
+        tile_storage,
+        tileset,
+        additional_tilesets,
+        animation_data,
+        mut shared_tile_data,
+    ) in &mut tilemap_layer_query
+    {
+        let chunk_size = tile_storage.chunk_size();
+        if shared_tile_data.chunk_size != chunk_size {
+            // The chunks are respawned with the new size, start over with an empty texture
+            *shared_tile_data = TilemapSharedTileData::with_chunk_size(chunk_size);
+        }
+
+        // Free the slots of despawned chunks
+        shared_tile_data
+            .retain_slots(|chunk_position| tilemap_layer.chunks.contains_key(&chunk_position));
 
-                *chunk_material = MeshMaterial2d(material);
+        let chunk_count = tilemap_layer.chunks.len() as u32;
+        if chunk_count == 0 {
+            continue;
+        }
+
+        let old_texture_size = shared_tile_data.texture_size();
+        if shared_tile_data.reserve(chunk_count) {
+            let texture_size = shared_tile_data.texture_size();
+            let old_image = core::mem::replace(
+                &mut shared_tile_data.image,
+                images.add(make_chunk_tile_data_image(&texture_size)),
+            );
+            // The texture only grows in height, so the slots keep their place and the written
+            // tiles carry over to the new texture
+            if old_texture_size.element_product() > 0 {
+                tile_data_writes.copies.push(TileDataCopy {
+                    source: old_image,
+                    destination: shared_tile_data.image.id(),
+                    size: old_texture_size,
+                });
+            }
+            if let Some(material) = chunk_materials.get_mut(shared_tile_data.material.id()) {
+                material.tile_data = shared_tile_data.image.clone();
             }
         }
 
-        tile_storage.clear_dirty_chunk_positions(chunk_positions_to_clear);
+        if !chunk_materials.contains(shared_tile_data.material.id()) {
+            shared_tile_data.material = chunk_materials.add(make_chunk_material(
+                tilemap_layer,
+                tileset,
+                additional_tilesets,
+                animation_data,
+                shared_tile_data.image.clone(),
+                chunk_size,
+                IVec2::ZERO,
+            ));
+        }
     }
 }
 
-fn make_chunk_tile_data_image(size: &UVec2, data: &[PackedTileData]) -> Image {
+fn make_chunk_material(
+    tilemap_layer: &TilemapLayer,
+    tileset: &Tileset,
+    additional_tilesets: Option<&AdditionalTilesets>,
+    animation_data: &TilesetAnimationData,
+    tile_data: Handle<Image>,
+    chunk_size: UVec2,
+    chunk_position: IVec2,
+) -> TilemapChunkMaterial {
+    let mut material = TilemapChunkMaterial {
+        alpha_mode: tilemap_layer.alpha_mode,
+        tileset: default(),
+        tileset_1: None,
+        tileset_2: None,
+        tileset_3: None,
+        tile_data,
+        animation_data: animation_data.0.clone(),
+        tilemap_info: TilemapInfo {
+            tileset_tile_sizes: [Vec4::ZERO; MAX_TILESETS],
+            tile_size: Vec2::ZERO,
+            chunk_size,
+            chunk_position,
+            layer_z_index: tilemap_layer.z_index,
+        },
+    };
+    set_chunk_material_tilesets(&mut material, tilemap_layer, tileset, additional_tilesets);
+    material
+}
+
+/// Binds the textures of a layer's tilesets to a chunk material, along with the size and offset
+/// of their tile quads.
+fn set_chunk_material_tilesets(
+    material: &mut TilemapChunkMaterial,
+    tilemap_layer: &TilemapLayer,
+    tileset: &Tileset,
+    additional_tilesets: Option<&AdditionalTilesets>,
+) {
+    let mut tileset_images: [Option<Handle<Image>>; MAX_TILESETS] = default();
+    let grid_size = tilemap_layer.grid_size_for(tileset).as_vec2();
+    let mut tileset_tile_sizes = [Vec4::ZERO; MAX_TILESETS];
+    for (slot, tileset) in iter_layer_tilesets(tileset, additional_tilesets).enumerate() {
+        tileset_images[slot] = Some(tileset.image.clone());
+        let quad = tileset_quad_rect(tileset, tilemap_layer.render_mode, grid_size);
+        tileset_tile_sizes[slot] = quad.size().extend(quad.min.x).extend(quad.min.y);
+    }
+    let [_, tileset_1, tileset_2, tileset_3] = tileset_images;
+
+    material.tileset = tileset.image.clone();
+    material.tileset_1 = tileset_1;
+    material.tileset_2 = tileset_2;
+    material.tileset_3 = tileset_3;
+    material.tilemap_info.tileset_tile_sizes = tileset_tile_sizes;
+    material.tilemap_info.tile_size = tileset.tile_size.as_vec2();
+}
+
+/// Creates a tile data texture of `size` tiles. Its tiles are written later, see
+/// [`TilemapChunkTileDataWrites`].
+///
+/// The texture is created without any data, which leaves it zeroed on the GPU. Zeroed tiles
+/// aren't visible, so the texture starts out empty without uploading anything.
+fn make_chunk_tile_data_image(size: &UVec2) -> Image {
     Image {
-        data: Some(bytemuck::cast_slice(data).to_vec()),
+        data: None,
         texture_descriptor: TextureDescriptor {
             size: Extent3d {
                 width: size.x,
@@ -291,89 +1040,117 @@ fn make_chunk_tile_data_image(size: &UVec2, data: &[PackedTileData]) -> Image {
             label: None,
             mip_level_count: 1,
             sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
             view_formats: &[],
         },
         sampler: ImageSampler::nearest(),
         texture_view_descriptor: None,
-        asset_usage: RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+        asset_usage: RenderAssetUsages::RENDER_WORLD,
     }
 }
 
-fn make_chunk_mesh(size: &UVec2, display_size: &Vec2, render_mode: TilemapRenderMode) -> Mesh {
+/// Returns the quad tiles of `tileset` are drawn in, relative to the bottom-left corner of their
+/// grid cell.
+fn tileset_quad_rect(tileset: &Tileset, render_mode: TilemapRenderMode, grid_size: Vec2) -> Rect {
+    let tile_size = tileset.tile_size.as_vec2();
+    let min = render_mode.tile_anchor(tile_size, grid_size) + tileset.tile_offset;
+    Rect::from_corners(min, min + tile_size)
+}
+
+/// Returns the area covered by the drawn quads of all tilesets in a layer, relative to the
+/// bottom-left corner of a grid cell.
+///
+/// Tiles can overhang their grid cell, so the chunk bounds have to cover the quads of every
+/// tileset rather than just the mesh.
+fn layer_quad_bounds(
+    tilemap_layer: &TilemapLayer,
+    tileset: &Tileset,
+    additional_tilesets: Option<&AdditionalTilesets>,
+) -> Rect {
+    let grid_size = tilemap_layer.grid_size_for(tileset).as_vec2();
+    iter_layer_tilesets(tileset, additional_tilesets)
+        .map(|tileset| tileset_quad_rect(tileset, tilemap_layer.render_mode, grid_size))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or_default()
+}
+
+/// Computes the bounds of a chunk mesh built by [`make_chunk_mesh`], given the bounds of the
+/// tile quads relative to their grid cell.
+fn make_chunk_aabb(
+    size: &UVec2,
+    grid_size: &Vec2,
+    render_mode: TilemapRenderMode,
+    origin: IVec2,
+    quad_bounds: Rect,
+) -> Aabb {
+    let origin_position = render_mode.tile_to_local(origin, *grid_size);
+
+    // Cell positions are linear in the tile position apart from the stagger of every other row
+    // or column, so the two outermost rows and columns are enough to find the extremes.
+    let edges = |size: u32| {
+        [0, 1, size.saturating_sub(2), size.saturating_sub(1)]
+            .map(|i| i.min(size.saturating_sub(1)))
+    };
+    let mut cell_bounds = Rect::EMPTY;
+    for y in edges(size.y) {
+        for x in edges(size.x) {
+            let tile_position = origin + UVec2::new(x, y).as_ivec2();
+            let cell_position = render_mode.tile_to_local(tile_position, *grid_size);
+            cell_bounds = cell_bounds.union_point(cell_position - origin_position);
+        }
+    }
+
+    Aabb::from_min_max(
+        (cell_bounds.min + quad_bounds.min).extend(0.0),
+        (cell_bounds.max + quad_bounds.max).extend(0.0),
+    )
+}
+
+fn make_chunk_mesh(
+    size: &UVec2,
+    tile_size: &Vec2,
+    grid_size: &Vec2,
+    render_mode: TilemapRenderMode,
+    origin: IVec2,
+) -> Mesh {
     let mut mesh = Mesh::new(
         PrimitiveTopology::TriangleList,
         RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
     );
 
     let num_quads = size.element_product() as usize;
-    let quad_size = display_size / size.as_vec2();
+    let origin_position = render_mode.tile_to_local(origin, *grid_size);
 
     let mut positions = Vec::with_capacity(4 * num_quads);
     let mut uvs = Vec::with_capacity(4 * num_quads);
     let mut indices = Vec::with_capacity(6 * num_quads);
 
-    match render_mode {
-        TilemapRenderMode::Orthogonal => {
-            for y in 0..size.y {
-                for x in 0..size.x {
-                    let i = positions.len() as u32;
-
-                    let p0 = quad_size * UVec2::new(x, y).as_vec2();
-                    let p1 = p0 + quad_size;
-
-                    positions.extend([
-                        Vec3::new(p0.x, p0.y, 0.0),
-                        Vec3::new(p1.x, p0.y, 0.0),
-                        Vec3::new(p0.x, p1.y, 0.0),
-                        Vec3::new(p1.x, p1.y, 0.0),
-                    ]);
-
-                    uvs.extend([
-                        Vec2::new(0.0, 1.0),
-                        Vec2::new(1.0, 1.0),
-                        Vec2::new(0.0, 0.0),
-                        Vec2::new(1.0, 0.0),
-                    ]);
-
-                    indices.extend([i, i + 2, i + 1]);
-                    indices.extend([i + 3, i + 1, i + 2]);
-                }
-            }
-        }
-        TilemapRenderMode::Isometric => {
-            for y in 0..size.y {
-                for x in 0..size.x {
-                    let i = positions.len() as u32;
-
-                    let tile_x = x as f32;
-                    let tile_y = y as f32;
-
-                    // TODO: Don't hardcode these offsets
-                    let iso_x = (tile_x - tile_y) * quad_size.x * 0.5;
-                    let iso_y = (tile_x + tile_y) * 8.0;
-
-                    let p0 = Vec2::new(iso_x, iso_y);
-                    let p1 = p0 + quad_size;
-
-                    positions.extend([
-                        Vec3::new(p0.x, p0.y, 0.0),
-                        Vec3::new(p1.x, p0.y, 0.0),
-                        Vec3::new(p0.x, p1.y, 0.0),
-                        Vec3::new(p1.x, p1.y, 0.0),
-                    ]);
-
-                    uvs.extend([
-                        Vec2::new(0.0, 1.0),
-                        Vec2::new(1.0, 1.0),
-                        Vec2::new(0.0, 0.0),
-                        Vec2::new(1.0, 0.0),
-                    ]);
-
-                    indices.extend([i, i + 2, i + 1]);
-                    indices.extend([i + 3, i + 1, i + 2]);
-                }
-            }
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let i = positions.len() as u32;
+
+            let tile_position = origin + UVec2::new(x, y).as_ivec2();
+            let p0 = render_mode.tile_to_local(tile_position, *grid_size) - origin_position;
+            let p1 = p0 + tile_size;
+
+            positions.extend([
+                Vec3::new(p0.x, p0.y, 0.0),
+                Vec3::new(p1.x, p0.y, 0.0),
+                Vec3::new(p0.x, p1.y, 0.0),
+                Vec3::new(p1.x, p1.y, 0.0),
+            ]);
+
+            uvs.extend([
+                Vec2::new(0.0, 1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+            ]);
+
+            indices.extend([i, i + 2, i + 1]);
+            indices.extend([i + 3, i + 1, i + 2]);
         }
     }
 
@@ -389,3 +1166,191 @@ fn make_chunk_mesh(size: &UVec2, display_size: &Vec2, render_mode: TilemapRender
 
     mesh
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+    use crate::{HexOrientation, StaggerIndex};
+
+    const RENDER_MODES: [TilemapRenderMode; 6] = [
+        TilemapRenderMode::Orthogonal,
+        TilemapRenderMode::Isometric,
+        TilemapRenderMode::Staggered {
+            stagger: StaggerIndex::Odd,
+        },
+        TilemapRenderMode::Staggered {
+            stagger: StaggerIndex::Even,
+        },
+        TilemapRenderMode::Hexagonal {
+            orientation: HexOrientation::PointyTop,
+            stagger: StaggerIndex::Odd,
+        },
+        TilemapRenderMode::Hexagonal {
+            orientation: HexOrientation::FlatTop,
+            stagger: StaggerIndex::Even,
+        },
+    ];
+
+    fn mesh_positions(mesh: &Mesh) -> &[[f32; 3]] {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("chunk mesh has no positions");
+        };
+        positions
+    }
+
+    #[test]
+    fn rectangular_chunk_mesh() {
+        let size = UVec2::new(5, 3);
+        let tile_size = Vec2::new(16.0, 24.0);
+        let mesh = make_chunk_mesh(
+            &size,
+            &tile_size,
+            &tile_size,
+            TilemapRenderMode::Orthogonal,
+            IVec2::ZERO,
+        );
+
+        let positions = mesh_positions(&mesh);
+        assert_eq!(positions.len(), 4 * 15);
+        assert_eq!(mesh.indices().map(Indices::len), Some(6 * 15));
+
+        let Some(VertexAttributeValues::Uint32(tile_indices)) =
+            mesh.attribute(ATTRIBUTE_TILE_INDEX)
+        else {
+            panic!("chunk mesh has no tile indices");
+        };
+
+        // Quads are laid out row by row, matching the chunk's tile data texture
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let index = (x + y * size.x) as usize;
+                let tile_indices = &tile_indices[index * 4..index * 4 + 4];
+                assert!(tile_indices.iter().all(|&i| i == index as u32));
+
+                let bottom_left = Vec3::from(positions[index * 4]).truncate();
+                assert_eq!(bottom_left, UVec2::new(x, y).as_vec2() * tile_size);
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_mesh_matches_tile_positions() {
+        let size = UVec2::new(7, 4);
+        let tile_size = Vec2::new(32.0, 32.0);
+        let grid_size = Vec2::new(32.0, 16.0);
+
+        for render_mode in RENDER_MODES {
+            for chunk_position in [IVec2::new(0, 0), IVec2::new(-3, 1), IVec2::new(1, -2)] {
+                let chunk_origin = chunk_position * size.as_ivec2();
+                let mesh_origin = render_mode.chunk_mesh_origin(chunk_origin);
+                let mesh = make_chunk_mesh(&size, &tile_size, &grid_size, render_mode, mesh_origin);
+                let chunk_translation = render_mode.tile_to_local(chunk_origin, grid_size);
+
+                let positions = mesh_positions(&mesh);
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        let index = (x + y * size.x) as usize;
+                        let tile_position = chunk_origin + UVec2::new(x, y).as_ivec2();
+                        let bottom_left = Vec3::from(positions[index * 4]).truncate();
+                        assert_eq!(
+                            chunk_translation + bottom_left,
+                            render_mode.tile_to_local(tile_position, grid_size),
+                            "{render_mode:?} tile {tile_position}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_aabb_contains_mesh() {
+        let tile_size = Vec2::new(32.0, 48.0);
+        let grid_size = Vec2::new(32.0, 16.0);
+
+        for size in [UVec2::new(1, 1), UVec2::new(2, 5), UVec2::new(8, 3)] {
+            for render_mode in RENDER_MODES {
+                for mesh_origin in [IVec2::ZERO, IVec2::ONE] {
+                    let mesh =
+                        make_chunk_mesh(&size, &tile_size, &grid_size, render_mode, mesh_origin);
+                    let aabb = make_chunk_aabb(
+                        &size,
+                        &grid_size,
+                        render_mode,
+                        mesh_origin,
+                        Rect::from_corners(Vec2::ZERO, tile_size),
+                    );
+
+                    let mut mesh_bounds = Rect::EMPTY;
+                    for position in mesh_positions(&mesh) {
+                        mesh_bounds = mesh_bounds.union_point(Vec3::from(*position).truncate());
+                    }
+                    assert_eq!(
+                        Vec3::from(aabb.min()).truncate(),
+                        mesh_bounds.min,
+                        "{render_mode:?} {size}"
+                    );
+                    assert_eq!(
+                        Vec3::from(aabb.max()).truncate(),
+                        mesh_bounds.max,
+                        "{render_mode:?} {size}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_aabb_covers_overhanging_tiles() {
+        let tileset = Tileset {
+            tile_size: UVec2::new(48, 64),
+            tile_offset: Vec2::new(-8.0, 4.0),
+            ..default()
+        };
+        let grid_size = Vec2::new(32.0, 16.0);
+        let size = UVec2::new(4, 3);
+
+        for render_mode in RENDER_MODES {
+            for chunk_position in [IVec2::ZERO, IVec2::new(-1, 2)] {
+                let mesh_origin = render_mode.chunk_mesh_origin(chunk_position * size.as_ivec2());
+                let mesh = make_chunk_mesh(
+                    &size,
+                    &tileset.tile_size.as_vec2(),
+                    &grid_size,
+                    render_mode,
+                    mesh_origin,
+                );
+                let quad = tileset_quad_rect(&tileset, render_mode, grid_size);
+                let aabb = make_chunk_aabb(&size, &grid_size, render_mode, mesh_origin, quad);
+
+                // The shader moves each quad of the mesh from the bottom-left corner of its cell
+                // to the tileset's quad
+                let mut drawn_bounds = Rect::EMPTY;
+                for quad_positions in mesh_positions(&mesh).chunks(4) {
+                    let cell_origin = quad_positions
+                        .iter()
+                        .map(|position| Vec3::from(*position).truncate())
+                        .reduce(Vec2::min)
+                        .unwrap();
+                    drawn_bounds = drawn_bounds
+                        .union_point(cell_origin + quad.min)
+                        .union_point(cell_origin + quad.max);
+                }
+                assert_eq!(
+                    Vec3::from(aabb.min()).truncate(),
+                    drawn_bounds.min,
+                    "{render_mode:?} {chunk_position}"
+                );
+                assert_eq!(
+                    Vec3::from(aabb.max()).truncate(),
+                    drawn_bounds.max,
+                    "{render_mode:?} {chunk_position}"
+                );
+            }
+        }
+    }
+}
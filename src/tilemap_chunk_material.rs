@@ -11,6 +11,8 @@ use bevy::{
     sprite::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin},
 };
 
+use super::MAX_TILESETS;
+
 pub const TILEMAP_CHUNK_MATERIAL_SHADER_HANDLE: Handle<Shader> =
     weak_handle!("40f33e62-82f8-4578-b3fa-f22989e7c4bb");
 
@@ -40,6 +42,9 @@ pub struct TilemapInfo {
     pub chunk_size: UVec2,
     pub chunk_position: IVec2,
     pub layer_z_index: i32,
+    /// Size of the tiles in each of the layer's tilesets in `xy`, and the offset of their quads
+    /// from the bottom-left corner of the grid cell in `zw`.
+    pub tileset_tile_sizes: [Vec4; MAX_TILESETS],
 }
 
 /// Material used for rendering tilemap chunks.
@@ -59,6 +64,18 @@ pub struct TilemapChunkMaterial {
 
     #[uniform(3)]
     pub tilemap_info: TilemapInfo,
+
+    #[texture(4, sample_type = "u_int")]
+    pub animation_data: Handle<Image>,
+
+    #[texture(5, dimension = "2d_array")]
+    pub tileset_1: Option<Handle<Image>>,
+
+    #[texture(6, dimension = "2d_array")]
+    pub tileset_2: Option<Handle<Image>>,
+
+    #[texture(7, dimension = "2d_array")]
+    pub tileset_3: Option<Handle<Image>>,
 }
 
 impl Material2d for TilemapChunkMaterial {
@@ -1,6 +1,6 @@
 #import bevy_sprite::{
     mesh2d_functions as mesh_functions,
-    mesh2d_view_bindings::view,
+    mesh2d_view_bindings::{globals, view},
 }
 
 struct Vertex {
@@ -13,17 +13,28 @@ struct Vertex {
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) uv: vec2<f32>,
-    @location(1) tile_index: u32,
+    @location(1) @interpolate(flat) tile_coord: vec2<u32>,
 }
 
-@group(2) @binding(0) var tileset: texture_2d_array<f32>;
+@group(2) @binding(0) var tileset_0: texture_2d_array<f32>;
 @group(2) @binding(1) var tileset_sampler: sampler;
 @group(2) @binding(2) var tile_data: texture_2d<u32>;
 @group(2) @binding(3) var<uniform> tilemap_info: TilemapInfo;
+@group(2) @binding(4) var animation_data: texture_2d<u32>;
+@group(2) @binding(5) var tileset_1: texture_2d_array<f32>;
+@group(2) @binding(6) var tileset_2: texture_2d_array<f32>;
+@group(2) @binding(7) var tileset_3: texture_2d_array<f32>;
+
+const MAX_TILESETS: u32 = 4u;
 
 struct TileData {
     tileset_index: u32,
+    tileset: u32,
     visible: bool,
+    flip_x: bool,
+    flip_y: bool,
+    flip_d: bool,
+    animated: bool,
     color: vec4<f32>,
 }
 
@@ -32,13 +43,19 @@ struct TilemapInfo {
     chunk_size: vec2<u32>,
     chunk_position: vec2<i32>,
     layer_z_index: i32,
+    tileset_tile_sizes: array<vec4<f32>, MAX_TILESETS>,
 }
 
 fn getTileData(coord: vec2<u32>) -> TileData {
     let data = textureLoad(tile_data, coord, 0);
 
     let tileset_index = data.r;
-    let visible = data.g != 0u;
+    let visible = (data.g & 1u) != 0u;
+    let flip_x = (data.g & 2u) != 0u;
+    let flip_y = (data.g & 4u) != 0u;
+    let flip_d = (data.g & 8u) != 0u;
+    let animated = (data.g & 16u) != 0u;
+    let tileset = (data.g >> 5u) & 3u;
 
     let color_r = f32(data.b & 0xFFu) / 255.0;
     let color_g = f32((data.b >> 8u) & 0xFFu) / 255.0;
@@ -47,34 +64,107 @@ fn getTileData(coord: vec2<u32>) -> TileData {
 
     let color = vec4<f32>(color_r, color_g, color_b, color_a);
 
-    return TileData(tileset_index, visible, color);
+    return TileData(tileset_index, tileset, visible, flip_x, flip_y, flip_d, animated, color);
+}
+
+// Chunks sharing a tile data texture each read from their own slot in it, selected by their
+// mesh tag. Chunks with their own texture always use slot 0.
+fn getTileCoord(instance_index: u32, tile_index: u32) -> vec2<u32> {
+    let chunk_size = tilemap_info.chunk_size;
+    let slots_per_row = max(textureDimensions(tile_data, 0).x / chunk_size.x, 1u);
+    let slot = mesh_functions::get_tag(instance_index);
+    let slot_origin = vec2<u32>(slot % slots_per_row, slot / slots_per_row) * chunk_size;
+    return slot_origin + vec2<u32>(tile_index % chunk_size.x, tile_index / chunk_size.x);
+}
+
+fn getAnimationWord(index: u32) -> u32 {
+    let width = textureDimensions(animation_data, 0).x;
+    return textureLoad(animation_data, vec2<u32>(index % width, index / width), 0).r;
+}
+
+// See `make_animation_data_image` for the layout of the animation data
+fn getAnimationFrame(tileset: u32, animation_index: u32) -> u32 {
+    let index = getAnimationWord(tileset) + animation_index;
+    if (index >= getAnimationWord(tileset + 1u)) {
+        return 0xffffu;
+    }
+
+    let header = MAX_TILESETS + 1u + index * 3u;
+    let frames_start = getAnimationWord(header);
+    let frame_count = getAnimationWord(header + 1u);
+    let duration_ms = getAnimationWord(header + 2u);
+    if (frame_count == 0u || duration_ms == 0u) {
+        return 0xffffu;
+    }
+
+    let time_ms = u32(globals.time * 1000.0) % duration_ms;
+    for (var i = 0u; i < frame_count; i++) {
+        let frame = frames_start + i * 2u;
+        if (time_ms < getAnimationWord(frame + 1u)) {
+            return getAnimationWord(frame);
+        }
+    }
+
+    return getAnimationWord(frames_start + (frame_count - 1u) * 2u);
+}
+
+fn sampleTileset(
+    tileset: u32,
+    uv: vec2<f32>,
+    tileset_index: u32,
+    ddx: vec2<f32>,
+    ddy: vec2<f32>
+) -> vec4<f32> {
+    switch tileset {
+        case 1u: {
+            return textureSampleGrad(tileset_1, tileset_sampler, uv, tileset_index, ddx, ddy);
+        }
+        case 2u: {
+            return textureSampleGrad(tileset_2, tileset_sampler, uv, tileset_index, ddx, ddy);
+        }
+        case 3u: {
+            return textureSampleGrad(tileset_3, tileset_sampler, uv, tileset_index, ddx, ddy);
+        }
+        default: {
+            return textureSampleGrad(tileset_0, tileset_sampler, uv, tileset_index, ddx, ddy);
+        }
+    }
 }
 
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
 
+    let tile_coord = getTileCoord(vertex.instance_index, vertex.tile_index);
+    let tile = getTileData(tile_coord);
+
+    // The mesh is built with one quad of the first tileset's tile size at the bottom-left corner
+    // of each grid cell. Move and resize it to the quad of the tile's tileset, which may overhang
+    // the cell.
+    let corner = vec2<f32>(vertex.uv.x, 1.0 - vertex.uv.y);
+    let tile_origin = vertex.position.xy - corner * tilemap_info.tile_size;
+    let tile_quad = tilemap_info.tileset_tile_sizes[tile.tileset];
+    let position = vec3<f32>(tile_origin + tile_quad.zw + corner * tile_quad.xy, vertex.position.z);
+
     let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
     let world_position = mesh_functions::mesh2d_position_local_to_world(
         world_from_local,
-        vec4<f32>(vertex.position, 1.0)
+        vec4<f32>(position, 1.0)
     );
 
     var clip_position = mesh_functions::mesh2d_position_world_to_clip(world_position);
 
-    // Calculate local tile coordinates within chunk
-    let chunk_size = textureDimensions(tile_data, 0);
-    let local_tile_coord = vec2<i32>(
-        i32(vertex.tile_index % chunk_size.x),
-        i32(vertex.tile_index / chunk_size.x)
+    // Bottom-left corner of the tile's grid cell, in world space. Sorting by its height keeps
+    // tiles further up the screen behind the ones below them in every render mode, regardless
+    // of how far they overhang their cell or which chunk they're in.
+    let tile_origin_world = mesh_functions::mesh2d_position_local_to_world(
+        world_from_local,
+        vec4<f32>(tile_origin, 0.0, 1.0)
     );
 
-    // Calculate GLOBAL tile coordinates
-    let global_tile_coord = tilemap_info.chunk_position * vec2<i32>(chunk_size) + local_tile_coord;
-
-    // Use global coordinates for cross-chunk depth sorting
+    // Use tile units for cross-chunk depth sorting
     // Add a large offset to ensure all values are positive
-    let tile_depth = f32(global_tile_coord.x + global_tile_coord.y) + 10000.0;
+    let tile_depth = tile_origin_world.y / tilemap_info.tile_size.y + 10000.0;
 
     // Layer separation
     let layer_offset = f32(tilemap_info.layer_z_index) * 100000.0;
@@ -85,26 +175,37 @@ fn vertex(vertex: Vertex) -> VertexOutput {
 
     out.position = clip_position;
     out.uv = vertex.uv;
-    out.tile_index = vertex.tile_index;
+    out.tile_coord = tile_coord;
 
     return out;
 }
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    let chunk_size = textureDimensions(tile_data, 0);
-    let tile_coord = vec2<u32>(
-        in.tile_index % chunk_size.x,
-        in.tile_index / chunk_size.x
-    );
+    let tile = getTileData(in.tile_coord);
 
-    let tile = getTileData(tile_coord);
+    var tileset_index = tile.tileset_index;
+    if (tile.animated) {
+        tileset_index = getAnimationFrame(tile.tileset, tile.tileset_index);
+    }
 
-    if (tile.tileset_index == 0xffffu || !tile.visible) {
+    if (tileset_index == 0xffffu || !tile.visible) {
         discard;
     }
 
-    let tex_color = textureSample(tileset, tileset_sampler, in.uv, tile.tileset_index);
+    // Diagonal flip first, then horizontal and vertical, matching Tiled
+    var uv = in.uv;
+    if (tile.flip_d) {
+        uv = uv.yx;
+    }
+    if (tile.flip_x) {
+        uv.x = 1.0 - uv.x;
+    }
+    if (tile.flip_y) {
+        uv.y = 1.0 - uv.y;
+    }
+
+    let tex_color = sampleTileset(tile.tileset, uv, tileset_index, dpdx(uv), dpdy(uv));
     let final_color = tex_color * tile.color;
 
     // Alpha-based visibility - discard if fully transparent
@@ -15,11 +15,43 @@ use glob::glob;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use super::TileAnimation;
+
 /// A component representing a tileset image containing all tile textures.
 #[derive(Component, Clone, Debug, Default)]
 pub struct Tileset {
     pub image: Handle<Image>,
     pub tile_size: UVec2,
+    /// Offset of the tiles' drawn quads from their anchor in the grid cell, in pixels.
+    ///
+    /// See [`TilemapRenderMode::tile_anchor`](crate::TilemapRenderMode::tile_anchor).
+    pub tile_offset: Vec2,
+    /// Animations that tiles can reference with [`TileData::from_animation`](crate::TileData::from_animation).
+    pub animations: Vec<TileAnimation>,
+}
+
+/// The maximum number of tilesets a single tilemap layer can draw from.
+pub const MAX_TILESETS: usize = 4;
+
+/// Tilesets a tilemap layer can draw from in addition to its [`Tileset`].
+///
+/// Tiles select a tileset with [`TileData::tileset`](crate::TileData::tileset), where `0` is the
+/// layer's [`Tileset`] and `1..` index into this list. Tilesets past [`MAX_TILESETS`] are ignored.
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct AdditionalTilesets(pub Vec<Tileset>);
+
+/// Iterates the tilesets of a tilemap layer in the order tiles refer to them.
+pub(crate) fn iter_layer_tilesets<'a>(
+    tileset: &'a Tileset,
+    additional_tilesets: Option<&'a AdditionalTilesets>,
+) -> impl Iterator<Item = &'a Tileset> {
+    core::iter::once(tileset)
+        .chain(
+            additional_tilesets
+                .into_iter()
+                .flat_map(|tilesets| tilesets.iter()),
+        )
+        .take(MAX_TILESETS)
 }
 
 #[derive(Serialize, Deserialize, Debug)]